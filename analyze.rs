@@ -0,0 +1,48 @@
+//! Level analysis: how many empty tubes a level needs, and how the shortest solution changes with more of them.
+use crate::astar::{self, SolveOptions, State};
+use crate::game::{Game, Tube};
+use crate::h10s;
+use crate::{take_option, with_input};
+
+/// `balls analyze LEVEL [--max-empty N]`
+///
+/// Solves the level's tubes of balls with each number of empty tubes from 0 to N (default 3, or as many as the level has),
+/// and prints whether each is solvable, the length of the shortest solution, and the number of nodes visited.
+/// A search that fails has visited every board reachable from the start, which proves there's no solution.
+pub fn main(mut args: Vec<String>) {
+    let max_empty = take_option(&mut args, "--max-empty").map(|max| max.parse().unwrap_or_else(|_| {
+        eprintln!("balls analyze: --max-empty must be a number of tubes, not {:?}", max);
+        std::process::exit(1)
+    }));
+    if args.len() != 1 {
+        eprintln!("balls analyze: Expected one argument: input filename (or '-' for stdin)");
+        std::process::exit(1);
+    }
+    let game = with_input(&args[0], Game::from_input);
+    // Partly filled tubes are kept as they are; only the empty tubes are taken away
+    let tubes: Vec<Tube> = game.tubes.iter().filter(|tube| tube.balls[0].is_some()).cloned().collect();
+    let given = game.tubes.len() - tubes.len();
+    let max_empty = max_empty.unwrap_or_else(|| given.max(3));
+    println!("{}: {} tubes with balls; the level gives {} more, empty", args[0], tubes.len(), given);
+
+    println!("{:>11}  {:<10}  {:>5}  {:>13}", "empty tubes", "result", "moves", "nodes visited");
+    let mut fewest = None;
+    for empty in 0..=max_empty {
+        let mut tubes = tubes.clone();
+        tubes.extend((0..empty).map(|_| Tube::empty()));
+        let (compressed, _palette) = Game{ tubes }.compress();
+        let outcome = astar::try_solve_until(compressed, State::is_solved, h10s::compressed_dig_clutter, &SolveOptions::default());
+        match outcome {
+            Ok((path, stats)) => {
+                println!("{:>11}  {:<10}  {:>5}  {:>13}", empty, "solvable", path.len(), stats.visited_len);
+                if fewest.is_none() { fewest = Some(empty); }
+            }
+            // Every reachable board was visited, none of them solved
+            Err(stats) => println!("{:>11}  {:<10}  {:>5}  {:>13}", empty, "unsolvable", "-", stats.visited_len),
+        }
+    }
+    match fewest {
+        Some(fewest) => println!("The fewest empty tubes that make it solvable: {}", fewest),
+        None => println!("It isn't solvable with up to {} empty tubes.", max_empty),
+    }
+}
@@ -1,10 +1,105 @@
 //! A* Search Algorithm implementation
 //!
 //! Contains no domain-specific knowledge about the ball-game.
-use std::collections::{BinaryHeap, HashSet};
-use std::cmp::Ordering;
-use std::{fmt,fmt::Debug};
-use std::hash::Hash;
+use std::collections::{BinaryHeap, HashMap, VecDeque, hash_map::Entry};
+use std::cmp::{Ordering, Reverse};
+use std::{fmt,fmt::Debug,fmt::Display};
+use std::hash::{BuildHasherDefault, Hash, Hasher};
+use std::ops::Add;
+use std::convert::TryFrom;
+
+/// The cost of a path: anything that can be added up and compared.
+///
+/// Implemented for the integer types, for f64 through FloatCost, and for pairs of costs through Lex.
+pub trait PathCost: Copy + Ord + Add<Output = Self> + Debug + Display {
+    /// The cost of the empty path
+    fn zero() -> Self;
+    /// The bucket index of this cost in the Buckets work queue, if it's a small enough non-negative integer.
+    /// Defaults to None, which means the cost can only go in the binary heap.
+    fn bucket(self) -> Option<usize> {
+        None
+    }
+}
+
+macro_rules! integer_path_cost {
+    ($($int:ty),*) => {$(
+        impl PathCost for $int {
+            fn zero() -> Self {
+                0
+            }
+            fn bucket(self) -> Option<usize> {
+                usize::try_from(self).ok()
+            }
+        }
+    )*};
+}
+integer_path_cost!(i32, i64, u32, u64, usize);
+
+/// A floating point cost, for domains with fractional costs, such as distances.
+///
+/// f64 isn't Ord, because of NaN; FloatCost is, by panicking on NaN. So don't make NaN costs.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct FloatCost(pub f64);
+
+impl Eq for FloatCost {}
+
+impl PartialOrd for FloatCost {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for FloatCost {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.partial_cmp(&other.0).expect("FloatCost can't compare NaN")
+    }
+}
+
+impl Add for FloatCost {
+    type Output = FloatCost;
+    fn add(self, other: FloatCost) -> FloatCost {
+        FloatCost(self.0 + other.0)
+    }
+}
+
+impl Display for FloatCost {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl PathCost for FloatCost {
+    fn zero() -> Self {
+        FloatCost(0.0)
+    }
+}
+
+/// A pair of costs compared lexicographically: the second only breaks ties in the first.
+///
+/// Searching with Lex costs finds the path that's cheapest by the first cost, and of those, cheapest by the second.
+/// A heuristic of Lex(h, zero) is admissable (and consistent) if h is, so long as the second costs are never negative.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Lex<A, B>(pub A, pub B);
+
+impl<A: PathCost, B: PathCost> Add for Lex<A, B> {
+    type Output = Lex<A, B>;
+    fn add(self, other: Lex<A, B>) -> Lex<A, B> {
+        Lex(self.0 + other.0, self.1 + other.1)
+    }
+}
+
+impl<A: Display, B: Display> Display for Lex<A, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}, then {}", self.0, self.1)
+    }
+}
+
+/// No bucket, as a bucket per first cost wouldn't order the nodes by their second cost.
+impl<A: PathCost, B: PathCost> PathCost for Lex<A, B> {
+    fn zero() -> Self {
+        Lex(A::zero(), B::zero())
+    }
+}
 
 /// An interface to expose a game's successor function to the search alg.
 ///
@@ -12,57 +107,366 @@ use std::hash::Hash;
 pub trait State: Sized + Hash + Eq + Clone + Debug {
     /// The search algorithm will return the path as a vector of Edges.
     type Edge: Clone + Clone + Debug;
+    /// The cost of an edge, and of a path. (i32 for the ball game)
+    type Cost: PathCost;
     /// An iterator over the neighboring states, their cost, and the 'Edge' to return if this is used as the solution Path.
-    type Iter: Iterator<Item = (Self, Cost, Self::Edge)>;
+    type Iter: Iterator<Item = (Self, Self::Cost, Self::Edge)>;
     /// Return an iterator over the neighboring states 
     fn iter_successors(self) -> Self::Iter;
     /// Take an edge, if that edge exists.
     fn try_edge(&self, edge: &Self::Edge) -> Option<Self>;
 
     fn is_solved(&self) -> bool;
+
+    /// A cheap 64-bit hash of the state, such as a Zobrist hash kept up to date by the successor function.
+    ///
+    /// If provided, the search keys its visited set by this hash, and only compares whole states when their hashes match.
+    /// Equal states must have equal hashes. Defaults to None, in which case states are hashed with their Hash impl.
+    fn zobrist(&self) -> Option<u64> {
+        None
+    }
 }
 
-/// A typedef for the integer I'm using to keep track of cost
-pub type Cost = i32;
+/// An optional mutating successor interface, for depth first searches that walk one state in place.
+///
+/// The successors of a State are new states, which for Game means a clone per successor.
+/// A MutState can instead list its edges, and take and take back an edge in place.
+pub trait MutState: State {
+    /// What `apply` returns, so `undo` can take the edge back.
+    type Undo;
+    /// Append the edges out of this state, and their costs, to `edges`.
+    /// The edges should be in the same order as iter_successors, so searches break ties the same way.
+    fn edges(&self, edges: &mut Vec<(Self::Edge, Self::Cost)>);
+    /// Take an edge listed by `edges`, in place.
+    fn apply(&mut self, edge: &Self::Edge) -> Self::Undo;
+    /// Take back the last edge applied.
+    fn undo(&mut self, undo: Self::Undo);
+}
 
 /// A path to be considered, ordered by astar_costs.
 struct Node<S: State> {
     /// cost + heuristic's predicted future cost
-    astar_cost: Cost,
+    astar_cost: S::Cost,
     /// Path cost to this path finding node
-    cost: Cost,
+    cost: S::Cost,
+    /// Orders nodes of equal astar_cost; the least is popped first. (See: TieBreak::key)
+    tie: (TieKey<S::Cost>, i64),
     /// edges leading to this state from the initial_state
     path: Vec<S::Edge>,
 }
 
 /// Statistics about how difficult a solution was to find
-pub struct SolveStats {
-    path_len: usize,
-    visited_len: usize,
-    work_queue_len: usize,
+pub struct SolveStats<C: PathCost> {
+    /// Number of edges in the solution
+    pub path_len: usize,
+    /// Total cost of the solution's edges
+    pub path_cost: C,
+    /// Number of distinct states expanded
+    pub visited_len: usize,
+    /// Number of nodes left in the work queue when the solution was found
+    pub work_queue_len: usize,
+    /// Number of successors never added to the work queue, as their state had already been reached as cheaply
+    pub duplicates_dropped: usize,
 }
-impl fmt::Display for SolveStats {
+impl<C: PathCost> SolveStats<C> {
+    /// The same stats, with the path cost converted by `f`.
+    pub fn map_cost<D: PathCost, F: FnOnce(C) -> D>(self, f: F) -> SolveStats<D> {
+        SolveStats {
+            path_len: self.path_len,
+            path_cost: f(self.path_cost),
+            visited_len: self.visited_len,
+            work_queue_len: self.work_queue_len,
+            duplicates_dropped: self.duplicates_dropped,
+        }
+    }
+}
+impl<C: PathCost> fmt::Display for SolveStats<C> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "Solved for {} long path by visiting {} nodes. work queue len: {}", self.path_len, self.visited_len, self.work_queue_len)
+        write!(f, "Solved for {} long path (costing {}) by visiting {} nodes. work queue len: {}. duplicates dropped: {}",
+            self.path_len, self.path_cost, self.visited_len, self.work_queue_len, self.duplicates_dropped)
+    }
+}
+
+/// A Hasher that passes a u64 through unchanged, for keys that are already hashes (See: State::zobrist)
+#[derive(Default)]
+pub struct IdentityHasher(u64);
+
+impl Hasher for IdentityHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+    fn write(&mut self, bytes: &[u8]) {
+        // Only meant for u64 keys, but stay correct for anything else
+        for &byte in bytes {
+            self.0 = self.0.rotate_left(8) ^ u64::from(byte);
+        }
+    }
+    fn write_u64(&mut self, hash: u64) {
+        self.0 = hash;
+    }
+}
+
+/// A map from states to values.
+///
+/// States with a zobrist hash are keyed by that hash, so finding one is a single lookup of a u64,
+/// and whole states are only compared when their hashes match.
+/// States whose hash collides with a different state's are kept aside in `collisions`.
+struct StateMap<S: State, V> {
+    hashed: HashMap<u64, (S, V), BuildHasherDefault<IdentityHasher>>,
+    collisions: HashMap<S, V>,
+    unhashed: HashMap<S, V>,
+}
+
+impl<S: State, V: Default> StateMap<S, V> {
+    fn new() -> Self {
+        StateMap { hashed: HashMap::default(), collisions: HashMap::new(), unhashed: HashMap::new() }
+    }
+
+    /// The value for `state`, inserting a default value if there's none yet.
+    fn get_mut(&mut self, state: &S) -> &mut V {
+        let map = match state.zobrist() {
+            Some(hash) => match self.hashed.entry(hash) {
+                Entry::Vacant(entry) => return &mut entry.insert((state.clone(), V::default())).1,
+                Entry::Occupied(entry) if entry.get().0 == *state => return &mut entry.into_mut().1,
+                Entry::Occupied(_) => &mut self.collisions,
+            },
+            None => &mut self.unhashed,
+        };
+        // Only clone the state to insert it
+        if !map.contains_key(state) {
+            map.insert(state.clone(), V::default());
+        }
+        map.get_mut(state).unwrap()
+    }
+}
+
+/// What the search knows about a state it has reached.
+struct Seen<C> {
+    /// The least path cost the state has been reached with
+    best_cost: Option<C>,
+    /// Whether the state has been expanded
+    visited: bool,
+}
+
+// derive(Default) would need C: Default
+impl<C> Default for Seen<C> {
+    fn default() -> Self {
+        Seen { best_cost: None, visited: false }
+    }
+}
+
+/// A solution's path, as the edges from the initial state, and the stats of the search that found it.
+pub type Solution<S> = (Vec<<S as State>::Edge>, SolveStats<<S as State>::Cost>);
+
+/// Which priority queue A* keeps its work queue in.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum WorkQueueKind {
+    /// std's BinaryHeap, which works for any costs
+    BinaryHeap,
+    /// An array of buckets, one per astar_cost, which is faster for small non-negative integer costs such as the ball game's.
+    /// Only for costs with a PathCost::bucket.
+    Buckets,
+}
+
+/// Which of the nodes with the least astar_cost A* expands first.
+///
+/// Any choice keeps the solution optimal, and every choice is deterministic:
+/// nodes are numbered in the order they're pushed, and the numbers settle any tie the policy leaves.
+/// Both work queues pop nodes in exactly the same order for the same policy.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TieBreak {
+    /// The first node pushed
+    Fifo,
+    /// The last node pushed
+    Lifo,
+    /// The node with the highest path cost, so the lowest heuristic; the last pushed of those.
+    /// Heads deeper into plateaus of equal astar_cost.
+    HighCost,
+    /// The node with the lowest path cost, so the highest heuristic; the last pushed of those.
+    LowCost,
+}
+
+/// The path cost part of a node's tie field, ordered so that the node to expand first is least.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum TieKey<C> {
+    /// The policy ignores path costs
+    Unused,
+    LowCost(C),
+    HighCost(Reverse<C>),
+}
+
+impl TieBreak {
+    /// The key of a node's tie field, for a node with path cost `cost` that is the `seq`th pushed.
+    fn key<C: PathCost>(self, cost: C, seq: usize) -> (TieKey<C>, i64) {
+        let seq = seq as i64;
+        match self {
+            TieBreak::Fifo => (TieKey::Unused, seq),
+            TieBreak::Lifo => (TieKey::Unused, -seq),
+            TieBreak::HighCost => (TieKey::HighCost(Reverse(cost)), -seq),
+            TieBreak::LowCost => (TieKey::LowCost(cost), -seq),
+        }
+    }
+}
+
+/// Options for astar::solve_with
+#[derive(Debug, Copy, Clone)]
+pub struct SolveOptions {
+    pub work_queue: WorkQueueKind,
+    pub tie_break: TieBreak,
+}
+
+impl Default for SolveOptions {
+    fn default() -> SolveOptions {
+        SolveOptions { work_queue: WorkQueueKind::BinaryHeap, tie_break: TieBreak::HighCost }
+    }
+}
+
+/// A priority queue of Nodes, which pops a Node with the least astar_cost first, and the least tie of those.
+trait WorkQueue<S: State> {
+    fn push(&mut self, node: Node<S>);
+    fn pop(&mut self) -> Option<Node<S>>;
+    fn len(&self) -> usize;
+}
+
+impl<S: State> WorkQueue<S> for BinaryHeap<Node<S>> {
+    fn push(&mut self, node: Node<S>) {
+        BinaryHeap::push(self, node)
+    }
+    fn pop(&mut self) -> Option<Node<S>> {
+        BinaryHeap::pop(self)
+    }
+    fn len(&self) -> usize {
+        BinaryHeap::len(self)
+    }
+}
+
+/// A bucket queue: a bucket of Nodes for each astar_cost, indexed by that cost. (See: WorkQueueKind::Buckets)
+///
+/// Each bucket is split by path cost for TieBreak::HighCost and LowCost, and is a single queue otherwise.
+/// Nodes are pushed in the order they're numbered, so the back of a queue is the last pushed.
+struct Buckets<S: State> {
+    buckets: Vec<Vec<VecDeque<Node<S>>>>,
+    tie_break: TieBreak,
+    /// No bucket below this one has any Nodes
+    min: usize,
+    len: usize,
+}
+
+impl<S: State> Buckets<S> {
+    fn new(tie_break: TieBreak) -> Self {
+        Buckets { buckets: vec![], tie_break, min: 0, len: 0 }
+    }
+}
+
+impl<S: State> WorkQueue<S> for Buckets<S> {
+    /// # Panics
+    ///
+    /// Panics if the node's astar_cost (or path cost) has no bucket, such as negative or fractional costs. (See: PathCost::bucket)
+    fn push(&mut self, node: Node<S>) {
+        let idx = node.astar_cost.bucket().unwrap_or_else(|| panic!("Buckets can't hold cost {}", node.astar_cost));
+        if idx >= self.buckets.len() {
+            self.buckets.resize_with(idx + 1, Vec::new);
+        }
+        let queue_idx = match self.tie_break {
+            TieBreak::Fifo | TieBreak::Lifo => 0,
+            TieBreak::HighCost | TieBreak::LowCost => node.cost.bucket().unwrap_or_else(|| panic!("Buckets can't hold cost {}", node.cost)),
+        };
+        let bucket = &mut self.buckets[idx];
+        if queue_idx >= bucket.len() {
+            bucket.resize_with(queue_idx + 1, VecDeque::new);
+        }
+        bucket[queue_idx].push_back(node);
+        // An inconsistent heuristic can push a node cheaper than the last one popped
+        self.min = self.min.min(idx);
+        self.len += 1;
+    }
+    fn pop(&mut self) -> Option<Node<S>> {
+        while self.min < self.buckets.len() {
+            let bucket = &mut self.buckets[self.min];
+            let queue = match self.tie_break {
+                TieBreak::Fifo | TieBreak::Lifo | TieBreak::LowCost => bucket.iter_mut().find(|queue| !queue.is_empty()),
+                TieBreak::HighCost => bucket.iter_mut().rev().find(|queue| !queue.is_empty()),
+            };
+            let node = match (queue, self.tie_break) {
+                (Some(queue), TieBreak::Fifo) => queue.pop_front(),
+                (Some(queue), _) => queue.pop_back(),
+                (None, _) => None,
+            };
+            if node.is_some() {
+                self.len -= 1;
+                return node;
+            }
+            self.min += 1;
+        }
+        None
+    }
+    fn len(&self) -> usize {
+        self.len
     }
 }
 
 /// A generic implementation of A*, which takes an initial state and a heuristic.
 ///
 /// Returns `None` if no solution is found, or `Some((Path, Stats))` otherwise
-pub fn solve<S: State, H: Fn(&S) -> Cost>(initial_state: S, heuristic: H) -> Option<(Vec<S::Edge>, SolveStats)> {
-    // the set of all states we've visited
-    let mut visited = HashSet::new();
-    // a priority queue, implemented using the standard library's binary heap.
-    let mut work_queue = BinaryHeap::<Node<S>>::new();
+pub fn solve<S: State, H: Fn(&S) -> S::Cost>(initial_state: S, heuristic: H) -> Option<Solution<S>> {
+    solve_with(initial_state, heuristic, &SolveOptions::default())
+}
+
+/// A*, as in astar::solve, with a choice of work queue and tie-breaking policy. (See: SolveOptions)
+pub fn solve_with<S: State, H: Fn(&S) -> S::Cost>(initial_state: S, heuristic: H, options: &SolveOptions) -> Option<Solution<S>> {
+    solve_until(initial_state, S::is_solved, heuristic, options)
+}
+
+/// A*, searching for a state that satisfies `goal`, rather than a solved state.
+///
+/// The heuristic must be admissible for `goal`, rather than for State::is_solved, for the path to be the shortest.
+pub fn solve_until<S: State, G: Fn(&S) -> bool, H: Fn(&S) -> S::Cost>(initial_state: S, goal: G, heuristic: H, options: &SolveOptions)
+    -> Option<Solution<S>>
+{
+    try_solve_until(initial_state, goal, heuristic, options).ok()
+}
+
+/// A*, as in astar::solve_until, but returning the stats of the search when no state meets the goal.
+///
+/// Failing means every state reachable from `initial_state` was visited, so the stats' visited_len counts them all.
+/// (path_len and path_cost are zero, and work_queue_len is zero, as the queue has run dry)
+pub fn try_solve_until<S: State, G: Fn(&S) -> bool, H: Fn(&S) -> S::Cost>(initial_state: S, goal: G, heuristic: H, options: &SolveOptions)
+    -> Result<Solution<S>, SolveStats<S::Cost>>
+{
+    match options.work_queue {
+        // a priority queue, implemented using the standard library's binary heap.
+        WorkQueueKind::BinaryHeap => search(initial_state, goal, heuristic, options.tie_break, BinaryHeap::new()),
+        WorkQueueKind::Buckets => search(initial_state, goal, heuristic, options.tie_break, Buckets::new(options.tie_break)),
+    }
+}
+
+/// A*, searching for the cheapest path from `initial_state` to `target`.
+///
+/// Returns `None` if `target` can't be reached, which means searching every state reachable from `initial_state`.
+pub fn solve_to<S: State, H: Fn(&S) -> S::Cost>(initial_state: S, target: &S, heuristic: H, options: &SolveOptions) -> Option<Solution<S>> {
+    solve_until(initial_state, |state| state == target, heuristic, options)
+}
+
+/// A* with the given goal and (empty) work queue.
+fn search<S: State, G: Fn(&S) -> bool, H: Fn(&S) -> S::Cost, Q: WorkQueue<S>>(initial_state: S, goal: G, heuristic: H, tie_break: TieBreak, mut work_queue: Q)
+    -> Result<Solution<S>, SolveStats<S::Cost>>
+{
+    // every state we've reached, and whether we've visited it.
+    let mut seen = StateMap::<S, Seen<S::Cost>>::new();
+    let mut visited_len = 0;
+    let mut duplicates_dropped = 0;
+    // The number of nodes pushed, which numbers them for tie_break
+    let mut pushed = 0;
     // Push our starting node
+    seen.get_mut(&initial_state).best_cost = Some(S::Cost::zero());
     work_queue.push(Node{
-        astar_cost: 0,
-        cost: 0,
+        astar_cost: S::Cost::zero(),
+        cost: S::Cost::zero(),
+        tie: tie_break.key(S::Cost::zero(), pushed),
         path: vec![]
     });
 
-    let mut last_cost = 0;
+    let mut last_cost = S::Cost::zero();
     // Loop over the work queue. Nodes with the least cost will be considered first.
     while let Some(work) = work_queue.pop() {
         // a useful assert I discovered all too late in development
@@ -71,7 +475,7 @@ pub fn solve<S: State, H: Fn(&S) -> Cost>(initial_state: S, heuristic: H) -> Opt
 
         // Break the fields of the "work" node out into variables cost and path while ignoring field 'astar_cost'
         // These are from the node we're coming from
-        let Node { cost, astar_cost: _, path } = work;
+        let Node { cost, astar_cost: _, tie: _, path } = work;
 
         // Follow the edges from the initial state to the state described by `work`.
         let state = {
@@ -82,46 +486,158 @@ pub fn solve<S: State, H: Fn(&S) -> Cost>(initial_state: S, heuristic: H) -> Opt
             state
         };
 
-        if state.is_solved() {
+        if goal(&state) {
             let stats = SolveStats{
                 path_len: path.len(),
+                path_cost: cost,
                 work_queue_len: work_queue.len(),
-                visited_len: visited.len()
+                visited_len,
+                duplicates_dropped,
             };
-            return Some((path, stats));
+            return Ok((path, stats));
         }
 
         // If we're the first to reach state
         // then the state's previous edge is the fastest route there
-        if visited.get(&state).is_none() {
-            visited.insert(state.clone());
-        }else{
+        let seen_state = seen.get_mut(&state);
+        if seen_state.visited {
             // already visited node, skip any further work
             continue;
         }
+        seen_state.visited = true;
+        visited_len += 1;
 
         for (next_state, edge_cost, edge) in state.clone().iter_successors() {
             let cost = cost + edge_cost;
+            // Drop the successor if it's already been reached as cheaply, which includes every visited state.
+            // (The heuristic is consistent, so a visited state was reached by its cheapest path)
+            // Otherwise the work queue fills with copies of the same state, which would be skipped when they're popped anyway.
+            let seen_next = seen.get_mut(&next_state);
+            match seen_next.best_cost {
+                Some(best_cost) if best_cost <= cost => { duplicates_dropped += 1; continue; }
+                _ => seen_next.best_cost = Some(cost),
+            }
+
             let mut new_path = Vec::with_capacity(path.len());
             new_path.extend(path.iter().cloned());
             new_path.push(edge.clone());
+            pushed += 1;
             let node = Node{
                 astar_cost: heuristic(&next_state) + cost,
                 cost,
+                tie: tie_break.key(cost, pushed),
                 path: new_path
             };
             work_queue.push(node);
         }
     }
-    None
+    Err(SolveStats{
+        path_len: 0,
+        path_cost: S::Cost::zero(),
+        work_queue_len: 0,
+        visited_len,
+        duplicates_dropped,
+    })
+}
+
+/// A generic implementation of IDA* (iterative deepening A*), which takes an initial state and an admissible heuristic.
+///
+/// Repeats a depth first search that abandons paths whose cost plus heuristic passes a bound,
+/// raising the bound each time to the least cost that passed it, until a solution is found.
+/// Needs only the current path in memory, and walks it with MutState's apply and undo, so nothing is cloned.
+/// If states have a zobrist hash, paths that return to a state already on the path are abandoned.
+///
+/// Returns `None` if no solution is found, or `Some((Path, Stats))` otherwise.
+/// visited_len counts every node expanded, over all of the iterations; There's no work queue, so work_queue_len and duplicates_dropped are 0.
+pub fn ida_solve<S: MutState, H: Fn(&S) -> S::Cost>(initial_state: S, heuristic: H) -> Option<Solution<S>> {
+    let mut search = IdaSearch {
+        state: initial_state,
+        heuristic,
+        path: vec![],
+        path_hashes: vec![],
+        edge_buffers: vec![],
+        expanded: 0,
+    };
+    search.path_hashes.extend(search.state.zobrist());
+    let mut bound = (search.heuristic)(&search.state);
+    loop {
+        match search.search(S::Cost::zero(), bound) {
+            Ok(path_cost) => {
+                let stats = SolveStats{
+                    path_len: search.path.len(),
+                    path_cost,
+                    visited_len: search.expanded,
+                    work_queue_len: 0,
+                    duplicates_dropped: 0,
+                };
+                return Some((search.path, stats));
+            }
+            Err(Some(next_bound)) => bound = next_bound,
+            // Nothing was cut off by the bound, so there's nowhere left to look
+            Err(None) => return None,
+        }
+    }
+}
+
+/// The state of an IDA* search, threaded through its recursion.
+struct IdaSearch<S: MutState, H> {
+    /// The state at the end of `path`
+    state: S,
+    heuristic: H,
+    /// edges leading to `state` from the initial state
+    path: Vec<S::Edge>,
+    /// zobrist hashes of the states along the path, including the initial state and `state`
+    path_hashes: Vec<u64>,
+    /// Spare edge lists, so each depth doesn't allocate its own
+    edge_buffers: Vec<Vec<(S::Edge, S::Cost)>>,
+    expanded: usize,
+}
+
+impl<S: MutState, H: Fn(&S) -> S::Cost> IdaSearch<S, H> {
+    /// Search below `self.state`, reached at `cost`, for a solution within `bound`.
+    ///
+    /// Returns Ok with its cost if `self.path` is now a solution, or otherwise the least astar cost past the bound, if any.
+    fn search(&mut self, cost: S::Cost, bound: S::Cost) -> Result<S::Cost, Option<S::Cost>> {
+        let astar_cost = cost + (self.heuristic)(&self.state);
+        if astar_cost > bound { return Err(Some(astar_cost)); }
+        if self.state.is_solved() { return Ok(cost); }
+        self.expanded += 1;
+
+        let mut edges = self.edge_buffers.pop().unwrap_or_default();
+        self.state.edges(&mut edges);
+        let mut next_bound: Option<S::Cost> = None;
+        for (edge, edge_cost) in edges.drain(..) {
+            let undo = self.state.apply(&edge);
+            let hash = self.state.zobrist();
+            if let Some(hash) = hash {
+                // A 64-bit hash collision could cut off a path that doesn't loop, but it's vanishingly unlikely.
+                if self.path_hashes.contains(&hash) {
+                    self.state.undo(undo);
+                    continue;
+                }
+                self.path_hashes.push(hash);
+            }
+            self.path.push(edge);
+            match self.search(cost + edge_cost, bound) {
+                Ok(path_cost) => return Ok(path_cost),
+                Err(Some(past_bound)) => next_bound = Some(next_bound.map_or(past_bound, |next_bound| next_bound.min(past_bound))),
+                Err(None) => {}
+            }
+            self.path.pop();
+            if hash.is_some() { self.path_hashes.pop(); }
+            self.state.undo(undo);
+        }
+        self.edge_buffers.push(edges);
+        Err(next_bound)
+    }
 }
 
-// manual trait implementations, to make it so Node's orderings only depend on the astar_cost field.
+// manual trait implementations, to make it so Node's orderings only depend on the astar_cost and tie fields.
 impl<S: State> Ord for Node<S> {
     fn cmp(&self, other: &Self) -> Ordering {
         // NOTE: In std's BinaryHeap the largest Node will be popped first, so this
         // code makes it so Node A > Node B if Node A's cost < Node B's cost
-        other.astar_cost.cmp(&self.astar_cost)
+        (other.astar_cost, other.tie).cmp(&(self.astar_cost, self.tie))
     }
 }
 
@@ -133,7 +649,7 @@ impl<S: State> PartialOrd for Node<S> {
 
 impl<S: State> PartialEq for Node<S> {
     fn eq(&self, other: &Self) -> bool {
-        self.astar_cost == other.astar_cost
+        (self.astar_cost, self.tie) == (other.astar_cost, other.tie)
     }
 }
 
@@ -6,7 +6,19 @@ use std::io::BufRead;
 use std::fmt;
 use std::collections::HashMap;
 use std::hash::Hash;
-use crate::astar::{Cost,State};
+use crate::astar::{self,MutState,SolveOptions,SolveStats,State};
+use crate::h10s;
+use crate::packed::PackedGame;
+use crate::palette::{self, Palette};
+
+/// A typedef for the integer I'm using to keep track of cost; Every move costs 1.
+pub type Cost = i32;
+
+/// The character used for an empty space in a level file's tube line, such as "RG..".
+pub const AIR: u8 = b'.';
+
+/// The character that begins a comment line, when doubled; It can't be a ball color, so a tube line is never a comment.
+pub const SLASH: u8 = b'/';
 
 /// A game state, consisting of a number of Tubes.
 #[derive(Clone, Hash, Eq, PartialEq)] // Automatically generate code implementing `Clone`, a common trait (interface) for a type to implement.
@@ -33,8 +45,61 @@ pub struct Ball {
 /// A game move, devoid of context.
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct Action {
-    from: u8,
-    to: u8,
+    /// Index of the tube the top ball is taken from
+    pub from: u8,
+    /// Index of the tube the ball is dropped into
+    pub to: u8,
+}
+
+/// What Game::apply returns, to take the move back with Game::undo.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Undo {
+    /// The action that was applied
+    pub action: Action,
+}
+
+/// Reasons an Action can't be taken, as returned by Game::try_move.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MoveError {
+    /// The source or destination tube doesn't exist
+    OutOfRange,
+    /// The source and destination are the same tube
+    NoOp,
+    /// The destination tube has no room for another ball
+    FullDestination,
+    /// The source tube has no ball to take
+    EmptySource,
+    /// The moved ball doesn't match the color of the destination's top ball
+    ColorMismatch { ball: Ball, onto: Ball },
+}
+
+/// What it takes to finish a game; Not every puzzle asks for the whole board to be sorted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Goal {
+    /// Every tube is empty or four balls of one color (See: Game::is_solved)
+    All,
+    /// Each of these colors fills a tube, whatever the rest of the board looks like
+    Colors(Vec<Ball>),
+    /// At least this many tubes are filled with four balls of one color
+    Tubes(usize),
+    /// Each color fills its own designated tube, given as (tube index, color) pairs.
+    /// Tubes and colors that aren't listed can end up anywhere.
+    Layout(Vec<(usize, Ball)>),
+}
+
+/// Reasons a Goal can never be met by a game, as returned by Game::check_goal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GoalError {
+    /// A targeted color isn't in the game
+    UnknownColor(Ball),
+    /// A color is targeted twice
+    RepeatedColor(Ball),
+    /// More tubes are to be filled than there are colors to fill them
+    TooManyTubes { tubes: usize, colors: usize },
+    /// A color is designated a tube that doesn't exist
+    NoSuchTube(usize),
+    /// A tube is designated more than one color
+    RepeatedTube(usize),
 }
 
 /// 
@@ -59,7 +124,10 @@ pub enum ValidationError {
 
 impl Game {
     /// Parse input text into a Game object.
-    /// See A1-input.txt as an example input
+    /// See A1-input.txt as an example input, and README.md's Level Format section for the details.
+    ///
+    /// Tube lines may be shorter than four balls, or contain '.' for empty spaces, to describe partly filled tubes.
+    /// The resulting game is checked by Game::validate, which rejects floating balls and colors without exactly four balls.
     ///
     /// # Panics
     ///
@@ -67,6 +135,20 @@ impl Game {
     // arg input is an object that implements the BufRead trait, which provides .read_line()
     // `-> Game` means we return a Game object.
     pub fn from_input(input: &mut dyn BufRead) -> Game {
+        Game::from_input_with_palette(input).0
+    }
+
+    /// Parse input text into a Game object and the Palette of its named colors.
+    /// See Game::from_input.
+    ///
+    /// Tube lines may also be written as whitespace separated tokens, each a color's name (or '.'),
+    /// such as `red blue blue green`, and may use any Unicode characters as colors, such as `🔴🔵🔵🟢`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if BufRead::read_line fails on `input`, and on malformed input.
+    pub fn from_input_with_palette(input: &mut dyn BufRead) -> (Game, Palette) {
+        let mut palette = Palette::default();
         let mut tubes = Vec::with_capacity(13);
         let mut tubes_remaining = 0; // # of full tubes left to parse
 
@@ -99,23 +181,30 @@ impl Game {
             if line.starts_with("//") { continue; }
 
             if !full_count_parsed {
-                // Line 1: # of full tubes
+                // Line 1: # of full tubes (more precisely, # of tube lines; they might not all be full)
                 full_count_parsed = true;
                 tubes_remaining = line.parse().expect("Couldn't parse full tube count");
             } else if !empty_count_parsed {
-                // Line 2: # of empty tubes
+                // Line 2: # of empty tubes, after the tube lines
                 empty_count_parsed = true;
                 empty_tubes = line.parse().expect("Couldn't parse empty tube count");
             } else if tubes_remaining > 0 {
                 tubes_remaining -= 1;
                 // Remaining lines: Colors of balls
-                if !line.is_ascii() { panic!("Unexpected non-ASCII character in ball color line. Line: {:?}", line); }
-                if line.bytes().count() != 4 { panic!("Expected exactly 4 ascii characters for ball colors, got line: {:?}", line); }
-                let mut colors = line.bytes();
-                let parse_ball = |colors: &mut std::str::Bytes| { // A closure (lambda function)
-                    let color = colors.next().unwrap();
-                    if !color.is_ascii_graphic() { panic!("Unprintable character used as ball color. Hex {:#?}", color); }
-                    Some(Ball{ color: NonZeroU8::new(color).unwrap() })
+                let tokens: Vec<&str> = if line.contains(char::is_whitespace) {
+                    // Token syntax: one color name per token
+                    line.split_whitespace().collect()
+                } else {
+                    // One character per ball
+                    palette::graphemes(&line)
+                };
+                if tokens.len() > 4 { panic!("Expected at most 4 ball colors, got line: {:?}", line); }
+                let mut colors = tokens.into_iter();
+                let mut parse_ball = |colors: &mut std::vec::IntoIter<&str>| { // A closure (lambda function)
+                    // A short line is a partly filled tube; the missing balls at the top are empty spaces
+                    let color = colors.next().unwrap_or(".");
+                    // '.' is an empty space, in a partly filled tube
+                    palette.color(color).unwrap_or_else(|e| panic!("{}", e))
                 };
                 tubes.push(Tube{
                     balls: [
@@ -141,9 +230,54 @@ impl Game {
         if let Err(e) = game.validate() {
             panic!("Invalid game board: {:?}", e);
         }
-        game // returns game object, as there's no semicolon
+        (game, palette) // returns game object and palette, as there's no semicolon
     }
 
+    /// Write the game in the level format, such that Game::from_input reads back the same game.
+    ///
+    /// Trailing empty tubes are counted in the header. Every other tube gets a line,
+    /// with '.' for each empty space, so partly filled and empty tubes keep their places.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a ball's color can't be written, such as in a compressed game.
+    pub fn to_text(&self) -> String {
+        self.to_text_with_palette(&Palette::default())
+    }
+
+    /// Write the game in the level format using the names in `palette`,
+    /// such that Game::from_input_with_palette reads back the same game (with the same names, though maybe not the same ids).
+    /// A compressed game written with the palette returned by Game::compress reads back as the decompressed game.
+    ///
+    /// If any color's name is longer than a character, tube lines are written in the token syntax.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a ball's color can't be written, such as in a compressed game.
+    pub fn to_text_with_palette(&self, palette: &Palette) -> String {
+        let empty_tubes = self.tubes.iter().rev().take_while(|tube| tube.balls[0].is_none()).count();
+        let lines = self.tubes.len() - empty_tubes;
+        let mut text = format!("{}\n{}\n", lines, empty_tubes);
+        for tube in &self.tubes[..lines] {
+            let tokens: Vec<String> = tube.balls.iter().map(|ball| match ball {
+                None => (AIR as char).to_string(),
+                Some(ball) => {
+                    let name = palette.name(*ball);
+                    if palette.lookup(&name) != Some(*ball) {
+                        panic!("Game::to_text: ball color {:#x} can't be written", ball.color.get());
+                    }
+                    name
+                }
+            }).collect();
+            text += &tokens.join(if palette.name_width() == 1 { "" } else { " " });
+            text.push('\n');
+        }
+        text
+    }
+
+    /// Take an action, returning the resulting state, or None if the action is illegal.
+    ///
+    /// This is the successor function's fast path; Use try_move to find out why an action is illegal.
     pub fn try_action(&self, action: Action) -> Option<Self> {
         let from = action.from as usize;
         let to = action.to as usize;
@@ -172,6 +306,132 @@ impl Game {
         Some(new_state)
     }
 
+    /// Take an action, returning the resulting state, or why the action is illegal.
+    pub fn try_move(&self, action: Action) -> Result<Self, MoveError> {
+        self.check_action(action)?;
+        Ok(self.try_action(action).expect("try_action rejected an action that check_action accepted"))
+    }
+
+    /// Take an action in place, returning what's needed to undo it.
+    ///
+    /// Unlike try_action, nothing is cloned, so a depth first search can walk the game with apply and undo alone.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the action is illegal (See: Game::check_action).
+    pub fn apply(&mut self, action: Action) -> Undo {
+        if let Err(e) = self.check_action(action) {
+            panic!("Can't apply {}: {}", action, e);
+        }
+        self.move_top(action.from as usize, action.to as usize);
+        Undo{ action }
+    }
+
+    /// Take back the move that returned `undo`, which must be the last move applied to this game.
+    pub fn undo(&mut self, undo: Undo) {
+        // The reverse move may break the color rule, but it restores the previous board
+        self.move_top(undo.action.to as usize, undo.action.from as usize);
+    }
+
+    /// Move the top ball of tube `from` to the top of tube `to`, without checking any rules.
+    fn move_top(&mut self, from: usize, to: usize) {
+        let from_idx = self.tubes[from].last()-1;
+        let to_idx = self.tubes[to].last();
+        self.tubes[to].balls[to_idx] = self.tubes[from].balls[from_idx].take();
+    }
+
+    /// Check whether an action can be taken, performing the same checks as try_action in the same order.
+    pub fn check_action(&self, action: Action) -> Result<(), MoveError> {
+        use MoveError::*;
+        let from = action.from as usize;
+        let to = action.to as usize;
+        let len = self.tubes.len();
+        if from >= len || to >= len { return Err(OutOfRange); }
+        if from == to { return Err(NoOp); }
+        if self.tubes[to].balls[3].is_some() { return Err(FullDestination); }
+        if self.tubes[from].balls[0].is_none() { return Err(EmptySource); }
+
+        let to_idx = self.tubes[to].last();
+        let from_idx = self.tubes[from].last()-1;
+        if to_idx > 0 {
+            // both tubes have a top ball at this point
+            let ball = self.tubes[from].balls[from_idx].unwrap();
+            let onto = self.tubes[to].balls[to_idx-1].unwrap();
+            if ball != onto { return Err(ColorMismatch{ ball, onto }); }
+        }
+        Ok(())
+    }
+
+    /// Find the shortest series of actions that solves this game.
+    ///
+    /// Uses the same search as the `balls` binary: A* with compressed_dig_clutter on a compressed copy of the game.
+    /// The returned actions apply to this (uncompressed) game, as compression doesn't change tube indices.
+    pub fn solve(&self) -> Option<(Vec<Action>, SolveStats<Cost>)> {
+        let (compressed_game, _palette) = self.clone().compress();
+        astar::solve(compressed_game, h10s::compressed_dig_clutter)
+    }
+
+    /// Find the shortest series of actions that turns this game into `target`.
+    ///
+    /// Uses A* with prefix_mismatch, on the uncompressed games (so `target` must use the same colors as this game).
+    /// Returns None right away if `target` has a different number of tubes, or different balls;
+    /// otherwise, finding that `target` can't be reached means searching every state that can be.
+    pub fn solve_to(&self, target: &Game) -> Option<(Vec<Action>, SolveStats<Cost>)> {
+        if self.tubes.len() != target.tubes.len() || self.ball_counts() != target.ball_counts() {
+            return None;
+        }
+        astar::solve_to(self.clone(), target, |game: &Game| h10s::prefix_mismatch(game, target), &SolveOptions::default())
+    }
+
+    /// The number of balls of each color.
+    pub fn ball_counts(&self) -> HashMap<Ball, usize> {
+        let mut counts = HashMap::new();
+        for ball in self.tubes.iter().flat_map(|tube| tube.balls.iter().filter_map(|&ball| ball)) {
+            *counts.entry(ball).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    /// Whether this game meets `goal`.
+    pub fn meets(&self, goal: &Goal) -> bool {
+        let full = |tube: &Tube| tube.balls[0].is_some() && tube.is_solved();
+        match goal {
+            Goal::All => self.is_solved(),
+            Goal::Colors(colors) => colors.iter().all(|&color| self.tubes.iter().any(|tube| tube.balls == [Some(color); 4])),
+            Goal::Tubes(tubes) => self.tubes.iter().filter(|tube| full(tube)).count() >= *tubes,
+            Goal::Layout(layout) => layout.iter().all(|&(idx, color)| self.tubes[idx].balls == [Some(color); 4]),
+        }
+    }
+
+    /// Check that `goal` only asks for colors this game has, and no more full tubes than it has colors;
+    /// And for a layout, that each color is designated one of the game's tubes, and no tube is designated two colors.
+    ///
+    /// A goal that passes can still be impossible to meet, if the moves to get there don't exist.
+    pub fn check_goal(&self, goal: &Goal) -> Result<(), GoalError> {
+        let counts = self.ball_counts();
+        match goal {
+            Goal::All => {}
+            Goal::Colors(colors) => {
+                for (idx, color) in colors.iter().enumerate() {
+                    if !counts.contains_key(color) { return Err(GoalError::UnknownColor(*color)); }
+                    if colors[..idx].contains(color) { return Err(GoalError::RepeatedColor(*color)); }
+                }
+            }
+            &Goal::Tubes(tubes) => if tubes > counts.len() {
+                return Err(GoalError::TooManyTubes{ tubes, colors: counts.len() });
+            },
+            Goal::Layout(layout) => {
+                for (idx, &(tube, color)) in layout.iter().enumerate() {
+                    if tube >= self.tubes.len() { return Err(GoalError::NoSuchTube(tube)); }
+                    if !counts.contains_key(&color) { return Err(GoalError::UnknownColor(color)); }
+                    if layout[..idx].iter().any(|&(other, _)| other == tube) { return Err(GoalError::RepeatedTube(tube)); }
+                    if layout[..idx].iter().any(|&(_, other)| other == color) { return Err(GoalError::RepeatedColor(color)); }
+                }
+            }
+        }
+        Ok(())
+    }
+
     /// Check if the game state follows the rules outlined in the assignment description.
     /// This operation is a tad costly, even using a hashmap; It can be written without one, but I wrote it as simply as possible.
     pub fn validate(&self) -> Result<(), ValidationError> {
@@ -212,35 +472,180 @@ println!("Not Enough '{}'", _ball);
 
     /// Replaces the printable colors with serialized ones, starting at 0x01.
     ///
-    /// Afterwards, the game is not displayable, but several heuristics can use a more efficient vector implementation instead of hashset.
-    pub fn compress(&mut self) {
-        let mut idx = 0u8;
-        // mapping from printable ASCII to new color id
-        let mut hm = HashMap::new();
-        // fill mapping
-        for tube in &self.tubes {
-            for ball in &tube.balls {
-                if let Some(ball) = ball {
-                    hm.entry(ball.color).or_insert_with(|| {
-                        idx += 1;
-                        NonZeroU8::new(idx).unwrap()
-                    });
-                }
+    /// Afterwards, several heuristics can use a more efficient vector implementation instead of hashset.
+    /// Those heuristics only accept a CompressedGame, which only this function (and compress_with_palette) produce.
+    /// The returned Palette names the new colors after the old ones, so the compressed game can still be displayed
+    /// (with Palette::display) and written (with Game::to_text_with_palette), and can be decompressed.
+    pub fn compress(self) -> (CompressedGame, Palette) {
+        self.compress_with_palette(&Palette::default())
+    }
+
+    /// Compress a game whose colors are named by `palette`; the returned Palette keeps those names.
+    /// See Game::compress.
+    pub fn compress_with_palette(self, palette: &Palette) -> (CompressedGame, Palette) {
+        let mut game = self;
+        let compressed_palette = palette.compress(&mut game);
+        (CompressedGame::new(game), compressed_palette)
+    }
+}
+
+/// A game whose colors have been numbered from 1 by Game::compress.
+///
+/// Derefs to the Game, for read-only access to the tubes.
+/// Keeps a Zobrist hash of the game, which try_action updates from the two slots a move changes.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct CompressedGame {
+    // private fields, so that only Game::compress can make one, and the hash stays in step with the game
+    game: Game,
+    zobrist: u64,
+}
+
+impl CompressedGame {
+    /// Wrap a game with compressed colors, hashing it from scratch.
+    fn new(game: Game) -> CompressedGame {
+        let zobrist = zobrist_hash(&game);
+        CompressedGame { game, zobrist }
+    }
+
+    /// Restore the original colors, given the Palette returned by Game::compress.
+    pub fn decompress(self, palette: &Palette) -> Game {
+        let mut game = self.game;
+        palette.decompress(&mut game);
+        game
+    }
+
+    /// See Game::try_action; compression doesn't change which actions are legal.
+    pub fn try_action(&self, action: Action) -> Option<Self> {
+        let game = self.game.try_action(action)?;
+        // The moved ball left the top of `from`, and is now the top of `to`
+        let (from, to) = (action.from as usize, action.to as usize);
+        let to_idx = game.tubes[to].last() - 1;
+        let from_idx = game.tubes[from].last();
+        let color = game.tubes[to].balls[to_idx].unwrap().color.get();
+        let zobrist = self.zobrist ^ zobrist_key(from, from_idx, color) ^ zobrist_key(to, to_idx, color);
+        Some(CompressedGame { game, zobrist })
+    }
+}
+
+/// The Zobrist key of a ball of `color` in slot `slot` of tube `tube`.
+///
+/// A game's Zobrist hash is the XOR of the keys of all of its balls, so moving a ball changes the hash by two keys.
+/// The keys are made on the fly by splitmix64, which is about as fast as looking them up in a table.
+pub fn zobrist_key(tube: usize, slot: usize, color: u8) -> u64 {
+    let mut key = ((tube as u64) << 16 | (slot as u64) << 8 | u64::from(color)).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    key = (key ^ (key >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    key = (key ^ (key >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    key ^ (key >> 31)
+}
+
+/// The Zobrist hash of a whole game, from scratch. (See: zobrist_key)
+pub fn zobrist_hash(game: &Game) -> u64 {
+    let mut hash = 0;
+    for (tube_idx, tube) in game.tubes.iter().enumerate() {
+        for (slot, ball) in tube.balls.iter().enumerate() {
+            if let Some(ball) = ball {
+                hash ^= zobrist_key(tube_idx, slot, ball.color.get());
             }
         }
-        // replace all colors according to `hm`
-        for tube in &mut self.tubes {
-            for ball in &mut tube.balls {
-                if let Some(ball) = ball {
-                    ball.color = hm[&ball.color];
+    }
+    hash
+}
+
+// Equal games have equal Zobrist hashes, so hashing just the Zobrist hash agrees with Eq.
+impl Hash for CompressedGame {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        state.write_u64(self.zobrist);
+    }
+}
+
+impl From<PackedGame> for CompressedGame {
+    fn from(packed: PackedGame) -> CompressedGame {
+        // PackedGames are only made from CompressedGames, so the colors are still compressed.
+        CompressedGame::new(packed.to_game())
+    }
+}
+
+impl std::ops::Deref for CompressedGame {
+    type Target = Game;
+    fn deref(&self) -> &Game {
+        &self.game
+    }
+}
+
+impl State for CompressedGame {
+    type Edge = Action;
+    type Cost = Cost;
+    type Iter = CompressedGameSuccessors;
+    fn iter_successors(self) -> CompressedGameSuccessors {
+        CompressedGameSuccessors {
+            state: self,
+            action: Action{ from: 0, to: 0 },
+        }
+    }
+    fn try_edge(&self, edge: &Action) -> Option<Self> {
+        self.try_action(*edge)
+    }
+    fn is_solved(&self) -> bool {
+        self.game.is_solved()
+    }
+    fn zobrist(&self) -> Option<u64> {
+        Some(self.zobrist)
+    }
+}
+
+impl MutState for CompressedGame {
+    type Undo = Undo;
+    fn edges(&self, edges: &mut Vec<(Action, Cost)>) {
+        self.game.edges(edges)
+    }
+    fn apply(&mut self, action: &Action) -> Undo {
+        let undo = self.game.apply(*action);
+        // The same two keys as in try_action
+        let (from, to) = (action.from as usize, action.to as usize);
+        let to_idx = self.game.tubes[to].last() - 1;
+        let color = self.game.tubes[to].balls[to_idx].unwrap().color.get();
+        self.zobrist ^= zobrist_key(from, self.game.tubes[from].last(), color) ^ zobrist_key(to, to_idx, color);
+        undo
+    }
+    fn undo(&mut self, undo: Undo) {
+        // XOR is its own inverse, so the keys are found and XORed again the same way as in apply
+        let (from, to) = (undo.action.from as usize, undo.action.to as usize);
+        let to_idx = self.game.tubes[to].last() - 1;
+        let color = self.game.tubes[to].balls[to_idx].unwrap().color.get();
+        self.zobrist ^= zobrist_key(from, self.game.tubes[from].last(), color) ^ zobrist_key(to, to_idx, color);
+        self.game.undo(undo);
+    }
+}
+
+/// An iterator over the successive states to a compressed ball game state, in the same order as GameSuccessors.
+pub struct CompressedGameSuccessors {
+    state: CompressedGame,
+    action: Action,
+}
+
+impl Iterator for CompressedGameSuccessors {
+    type Item = (CompressedGame, Cost, Action);
+    fn next(&mut self) -> Option<Self::Item> {
+        let len = self.state.tubes.len() as u8;
+        while self.action.from < len {
+            while self.action.to < len {
+                let action = self.action;
+                self.action.to += 1;
+                if let Some(new_state) = self.state.try_action(action) {
+                    // cost of all moves in ball game is 1.
+                    return Some((new_state, 1, action));
                 }
             }
+            self.action.to = 0;
+            self.action.from += 1;
         }
+        None
     }
 }
 
 impl State for Game {
     type Edge = Action;
+    type Cost = Cost;
     type Iter = GameSuccessors;
     fn iter_successors(self) -> GameSuccessors {
         GameSuccessors {
@@ -264,6 +669,27 @@ impl State for Game {
     }
 }
 
+impl MutState for Game {
+    type Undo = Undo;
+    /// The legal actions, in the same order as GameSuccessors.
+    fn edges(&self, edges: &mut Vec<(Action, Cost)>) {
+        let len = self.tubes.len() as u8;
+        for from in 0..len {
+            for to in 0..len {
+                let action = Action{ from, to };
+                // cost of all moves in ball game is 1.
+                if self.check_action(action).is_ok() { edges.push((action, 1)); }
+            }
+        }
+    }
+    fn apply(&mut self, action: &Action) -> Undo {
+        Game::apply(self, *action)
+    }
+    fn undo(&mut self, undo: Undo) {
+        Game::undo(self, undo)
+    }
+}
+
 /// An iterator over the successive states to a ball game state.
 pub struct GameSuccessors {
     state: Game,
@@ -300,6 +726,23 @@ impl Iterator for GameSuccessors {
     }
 }
 
+impl Action {
+    /// Parse a move written as "from->to", such as `3->5`.
+    ///
+    /// If `one_based` is set, the tube numbers count from 1 rather than from 0 (as shown by Game's Display).
+    /// Returns None if the text isn't a move.
+    pub fn parse(text: &str, one_based: bool) -> Option<Action> {
+        let mut numbers = text.splitn(2, "->");
+        let from: u8 = numbers.next()?.trim().parse().ok()?;
+        let to: u8 = numbers.next()?.trim().parse().ok()?;
+        if one_based {
+            Some(Action{ from: from.checked_sub(1)?, to: to.checked_sub(1)? })
+        } else {
+            Some(Action{ from, to })
+        }
+    }
+}
+
 impl Tube {
     pub fn empty() -> Tube {
         Tube{ balls: [None; 4] }
@@ -330,6 +773,11 @@ impl fmt::Debug for Game {
         write!(f, "{}", self)
     }
 }
+impl fmt::Debug for Ball {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
 impl fmt::Display for Game {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
 /*
@@ -364,11 +812,61 @@ impl fmt::Display for Tube {
     }
 }
 
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use MoveError::*;
+        match self {
+            OutOfRange => write!(f, "no such tube"),
+            NoOp => write!(f, "source and destination are the same tube"),
+            FullDestination => write!(f, "destination tube is full"),
+            EmptySource => write!(f, "source tube is empty"),
+            ColorMismatch{ ball, onto } => write!(f, "can't put a '{}' ball onto a '{}' ball", ball, onto),
+        }
+    }
+}
+
+impl fmt::Display for GoalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use GoalError::*;
+        match self {
+            UnknownColor(ball) => write!(f, "there are no '{}' balls", ball),
+            RepeatedColor(ball) => write!(f, "'{}' is targeted more than once", ball),
+            TooManyTubes{ tubes, colors } => write!(f, "can't fill {} tubes with only {} colors", tubes, colors),
+            NoSuchTube(idx) => write!(f, "there's no tube {}", idx),
+            RepeatedTube(idx) => write!(f, "tube {} is designated more than one color", idx),
+        }
+    }
+}
+
+impl MoveError {
+    /// Describe the error like its Display impl, but naming the balls' colors by `palette`.
+    ///
+    /// Display shows a ball by its color id, which for a named color isn't its name (See: palette.rs).
+    pub fn to_string_with_palette(self, palette: &Palette) -> String {
+        match self {
+            MoveError::ColorMismatch{ ball, onto } =>
+                format!("can't put a '{}' ball onto a '{}' ball", palette.name(ball), palette.name(onto)),
+            e => e.to_string(),
+        }
+    }
+}
+
+/// Formats the action as "from->to", with zero-based tube numbers.
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}->{}", self.from, self.to)
+    }
+}
+
+/// Formats compressed colors as a hex digit, and ASCII colors as themselves.
+/// Named colors (ids from 0x80) have no name without their Palette, so they're written as their id, like "#80".
 impl fmt::Display for Ball {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let c = self.color.get();
         if c < 0x10 {
             write!(f, "{:x}", c)
+        } else if c >= 0x80 {
+            write!(f, "#{:x}", c)
         } else {
             write!(f, "{}", std::char::from_u32(c.into()).unwrap())
         }
@@ -4,6 +4,7 @@
 #![allow(unused)] // not every heuristic is used, and that's O-K
 use crate::game::*;
 use crate::astar::*;
+use crate::packed::{self, PackedGame};
 use std::collections::HashSet;
 
 // NOTES ABOUT THE STATS:
@@ -78,10 +79,8 @@ pub fn diggly(game: &Game) -> Cost {
 
 /// Same heuristic as diggly, but is implemented without a hashset for greater performance.
 ///
-/// # Panics
-///
-/// Panics if balls' colors are not compressed. (See: main.rs compress_game)
-pub fn compressed_diggly(game: &Game) -> Cost {
+/// Takes a CompressedGame, as the balls' colors are used as indices. (See: Game::compress)
+pub fn compressed_diggly(game: &CompressedGame) -> Cost {
     // if there are N tubes, there are at most N-1 colors
     let mut seen = vec![false; game.tubes.len()];
     game.tubes.iter().map(|tube| {
@@ -125,10 +124,8 @@ pub fn dig_clutter(game: &Game) -> Cost {
 
 /// Same heuristic as dig_clutter, but is implemented without a hashset for greater performance
 ///
-/// # Panics
-///
-/// Panics if balls' colors are not compressed. (See: main.rs compress_game)
-pub fn compressed_dig_clutter(game: &Game) -> Cost {
+/// Takes a CompressedGame, as the balls' colors are used as indices. (See: Game::compress)
+pub fn compressed_dig_clutter(game: &CompressedGame) -> Cost {
     // NOTE: this `seen` vector's 0 position goes unused. could be avoided, but reduces readability of below code.
     let mut seen = vec![false; game.tubes.len()];
     game.tubes.iter().map(|tube| {
@@ -148,12 +145,128 @@ pub fn compressed_dig_clutter(game: &Game) -> Cost {
         }).sum()
 }
 
-/// Bad heuristic, inadmissable, maybe solve phone game?
+/// compressed_dig_clutter for moves of different costs: each ball it counts is charged the cheapest move out of its tube.
+///
+/// `min_out` is the cheapest move out of each tube (See: weighted::CostModel::min_out).
+/// Admissable: Each ball counted has to leave its tube at least once, and each move moves one ball.
+/// Of the tubes with the same color at the bottom, all but one have to give up that ball;
+/// Not knowing which, it charges the cheapest of them, which is all but the most expensive.
+/// With every move costing 1, it's the same as compressed_dig_clutter.
+/// Consistent, as a move only lowers the cost by (at most) the cheapest move out of the tube it moves the ball from.
+pub fn weighted_dig_clutter(game: &CompressedGame, min_out: &[Cost]) -> Cost {
+    // total and most expensive min_out of the tubes with each color at the bottom, indexed by color
+    let mut bottoms = vec![(0, 0); game.tubes.len() + 1];
+    let garbage: Cost = game.tubes.iter().zip(min_out).map(|(tube, &out)| {
+            if let Some(ball) = tube.balls[0] {
+                let (total, max) = &mut bottoms[ball.color.get() as usize];
+                *total += out;
+                *max = out.max(*max);
+            }
+            // penalize balls not having a continous streak of one color connecting to the bottom
+            tube.balls.iter().zip(tube.balls.iter().skip(1))
+            .fold((0, false), |(cost, broken), (ball1, ball2)| {
+                if ball2.is_some() && ( broken || ball1!=ball2 ) {
+                    (cost + out, true) // garbage balls we'll need to move
+                } else {
+                    (cost, false) // haven't broken continous streak, or we're in the air
+                }
+            }).0
+        }).sum();
+    // penalize multiple tubes' bottom ball being the same color
+    garbage + bottoms.iter().map(|(total, max)| total - max).sum::<Cost>()
+}
+
+/// Same heuristic as compressed_dig_clutter, for the bit-packed representation.
+///
+/// Uses a bitmask as the `seen` set, so nothing is allocated.
+pub fn packed_dig_clutter(game: &PackedGame) -> Cost {
+    let mut seen = 0u16;
+    game.tubes().iter().map(|&tube| {
+            let bottom = packed::ball_at(tube, 0);
+            // penalize balls not having a continous streak of one color connecting to the bottom
+            let mut cost = (1..packed::height(tube))
+                .skip_while(|&slot| packed::ball_at(tube, slot) == bottom)
+                .count() as Cost;
+            // penalize multiple tubes' bottom ball being the same color
+            if bottom != 0 {
+                if seen & 1 << bottom != 0 { cost += 1; } else { seen |= 1 << bottom; }
+            }
+            cost
+        }).sum()
+}
+
+/// Heuristic for reaching `target`, rather than solving: the balls that aren't already where they are in `target`.
+///
+/// Admissable: In each tube, the balls above the longest run from the bottom that matches the target tube
+/// will each need to be moved out at least once, and the target's balls above that run will each need to be moved in.
+/// A move takes one ball out of one tube and into another, so the larger of those two totals is a lower bound.
+/// It's consistent too, as a move changes each total by at most one.
+pub fn prefix_mismatch(game: &Game, target: &Game) -> Cost {
+    let (mut out, mut into) = (0, 0);
+    for (tube, target_tube) in game.tubes.iter().zip(&target.tubes) {
+        let matching = tube.balls.iter().zip(&target_tube.balls)
+            .take_while(|(ball, target_ball)| ball.is_some() && ball == target_ball)
+            .count();
+        let height = tube.balls.iter().filter(|ball| ball.is_some()).count();
+        let target_height = target_tube.balls.iter().filter(|ball| ball.is_some()).count();
+        out += (height - matching) as Cost;
+        into += (target_height - matching) as Cost;
+    }
+    out.max(into)
+}
+
+/// Heuristic for a partial Goal: the moves each targeted color needs to fill the tube it's closest to filling.
 ///
-/// # Panics
+/// To fill a tube, every ball of the color that isn't in the run of that color at the bottom of the tube needs moving in,
+/// and those of its balls sitting higher up in the same tube need moving out before that.
+/// Admissable: each move moves a ball of one color, and each color fills a tube no cheaper than the cheapest tube.
+/// For Goal::Tubes(n), any n colors might be the ones to fill tubes, so only the n cheapest are charged.
+/// It doesn't charge for other colors' balls in the way, as the move out of the way might be that ball's move into its own tube.
+/// Consistent, as a move changes only the moved ball's color's costs, and only by one.
+///
+/// For a Goal::Layout, each color's tube is already known, so that tube is the one it's charged for (See: layout_streaks).
+pub fn goal_streaks(game: &Game, goal: &Goal) -> Cost {
+    let colors: Vec<Ball> = match goal {
+        Goal::Colors(colors) => colors.clone(),
+        Goal::All | Goal::Tubes(_) => game.ball_counts().keys().cloned().collect(),
+        Goal::Layout(layout) => return layout_streaks(game, layout),
+    };
+    let mut costs: Vec<Cost> = colors.iter().map(|&color| {
+        game.tubes.iter().map(|tube| {
+            let streak = tube.balls.iter().take_while(|&&ball| ball == Some(color)).count();
+            let above = tube.balls[streak..].iter().filter(|&&ball| ball == Some(color)).count();
+            (4 - streak + above) as Cost
+        }).min().unwrap_or(0)
+    }).collect();
+    if let Goal::Tubes(tubes) = goal {
+        costs.sort();
+        costs.truncate(*tubes);
+    }
+    costs.iter().sum()
+}
+
+/// Heuristic for a Goal::Layout: balls that aren't yet at the bottom of their designated tube, plus the balls in the way.
+///
+/// Like goal_streaks, each designated color's balls outside the run at the bottom of its tube need moving in,
+/// and those higher up in its tube need moving out first.
+/// Balls of colors without a designated tube that sit above that run need moving out of the way too;
+/// Unlike other colors' balls, that move can't double as moving them into their own tube.
+/// Admissable, as no move is counted twice; And consistent, as a move changes the cost by at most one.
+pub fn layout_streaks(game: &Game, layout: &[(usize, Ball)]) -> Cost {
+    layout.iter().map(|&(idx, color)| {
+        let balls = &game.tubes[idx].balls;
+        let streak = balls.iter().take_while(|&&ball| ball == Some(color)).count();
+        let blockers = balls[streak..].iter().filter_map(|&ball| ball)
+            .filter(|&ball| ball == color || layout.iter().all(|&(_, designated)| designated != ball))
+            .count();
+        (4 - streak + blockers) as Cost
+    }).sum()
+}
+
+/// Bad heuristic, inadmissable, maybe solve phone game?
 ///
-/// Panics if balls' colors are not compressed. (See: main.rs compress_game)
-pub fn teenagent(game: &Game) -> Cost {
+/// Takes a CompressedGame, like compressed_dig_clutter.
+pub fn teenagent(game: &CompressedGame) -> Cost {
     compressed_dig_clutter(game) + consecutive_enjoyer(game) / 2
 }
 
@@ -0,0 +1,289 @@
+//! A minimal JSON reader and writer, for output meant to be read by scripts.
+//!
+//! The schemas of the documents written by balls are described in README.md, under "JSON Output".
+use crate::astar::SolveStats;
+use crate::game::{Action, Cost, Game, Tube};
+use crate::palette::Palette;
+use std::fmt;
+
+/// A JSON value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(i64),
+    String(String),
+    Array(Vec<Json>),
+    /// Members are written in order
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    /// Parse a JSON document.
+    ///
+    /// Numbers must be integers, as balls never writes any other kind.
+    pub fn parse(text: &str) -> Result<Json, String> {
+        let mut parser = Parser { text: text.as_bytes(), pos: 0 };
+        let value = parser.value()?;
+        parser.whitespace();
+        if parser.pos != parser.text.len() {
+            return Err(parser.error("trailing characters after JSON value"));
+        }
+        Ok(value)
+    }
+
+    /// Build an object from its members, in order.
+    pub fn object(members: Vec<(&str, Json)>) -> Json {
+        Json::Object(members.into_iter().map(|(key, value)| (key.to_string(), value)).collect())
+    }
+}
+
+/// A board, as an array of tubes. Each tube is an array of its balls' color names, from the bottom ball up.
+/// Empty spaces are left out, so an empty tube is an empty array.
+pub fn game(game: &Game, palette: &Palette) -> Json {
+    Json::Array(game.tubes.iter().map(|tube| {
+        Json::Array(tube.balls.iter().filter_map(|&ball| ball).map(|ball| Json::String(palette.name(ball))).collect())
+    }).collect())
+}
+
+/// Read a board written by `game`, checking that it is a valid game (See: Game::validate).
+///
+/// Colors are read like the tokens of a level file, so names longer than one character are added to the returned Palette.
+pub fn to_game(json: &Json) -> Result<(Game, Palette), String> {
+    let tubes = match json {
+        Json::Array(tubes) => tubes,
+        _ => return Err("a board must be an array of tubes".to_string()),
+    };
+    let mut palette = Palette::default();
+    let mut game = Game { tubes: Vec::with_capacity(tubes.len()) };
+    for tube in tubes {
+        let balls = match tube {
+            Json::Array(balls) if balls.len() <= 4 => balls,
+            _ => return Err(format!("a tube must be an array of at most 4 colors, not {}", tube)),
+        };
+        let mut new_tube = Tube::empty();
+        for (slot, ball) in balls.iter().enumerate() {
+            new_tube.balls[slot] = match ball {
+                Json::String(color) => match palette.color(color)? {
+                    Some(ball) => Some(ball),
+                    None => return Err("empty spaces are left out of tubes, rather than written as \".\"".to_string()),
+                },
+                _ => return Err(format!("a ball's color must be a string, not {}", ball)),
+            };
+        }
+        game.tubes.push(new_tube);
+    }
+    game.validate().map_err(|e| format!("invalid game board: {:?}", e))?;
+    Ok((game, palette))
+}
+
+/// An action, as an object with zero-based "from" and "to" tube numbers.
+pub fn action(action: Action) -> Json {
+    Json::object(vec![
+        ("from", Json::Number(action.from.into())),
+        ("to", Json::Number(action.to.into())),
+    ])
+}
+
+/// SolveStats, as an object with a member per field.
+pub fn stats(stats: &SolveStats<Cost>) -> Json {
+    Json::object(vec![
+        ("path_len", Json::Number(stats.path_len as i64)),
+        ("path_cost", Json::Number(i64::from(stats.path_cost))),
+        ("visited_len", Json::Number(stats.visited_len as i64)),
+        ("work_queue_len", Json::Number(stats.work_queue_len as i64)),
+        ("duplicates_dropped", Json::Number(stats.duplicates_dropped as i64)),
+    ])
+}
+
+/// Writes the value compactly, on one line.
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(b) => write!(f, "{}", b),
+            Json::Number(n) => write!(f, "{}", n),
+            Json::String(s) => write_string(f, s),
+            Json::Array(items) => {
+                write!(f, "[")?;
+                for (idx, item) in items.iter().enumerate() {
+                    if idx > 0 { write!(f, ",")?; }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+            Json::Object(members) => {
+                write!(f, "{{")?;
+                for (idx, (key, value)) in members.iter().enumerate() {
+                    if idx > 0 { write!(f, ",")?; }
+                    write_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+/// Write a string literal, escaping quotes, backslashes, and control characters.
+fn write_string(f: &mut fmt::Formatter<'_>, s: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+/// A recursive descent parser over the bytes of a JSON document.
+struct Parser<'a> {
+    text: &'a [u8],
+    /// index of the next unread byte
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, message: &str) -> String {
+        format!("{} at byte {}", message, self.pos)
+    }
+
+    fn whitespace(&mut self) {
+        while self.pos < self.text.len() && b" \t\r\n".contains(&self.text[self.pos]) {
+            self.pos += 1;
+        }
+    }
+
+    /// Skip whitespace, then return the next byte without consuming it
+    fn peek(&mut self) -> Option<u8> {
+        self.whitespace();
+        self.text.get(self.pos).cloned()
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), String> {
+        if self.peek() != Some(byte) {
+            return Err(self.error(&format!("expected '{}'", byte as char)));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn keyword(&mut self, keyword: &str, value: Json) -> Result<Json, String> {
+        if !self.text[self.pos..].starts_with(keyword.as_bytes()) {
+            return Err(self.error("unexpected character"));
+        }
+        self.pos += keyword.len();
+        Ok(value)
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        match self.peek() {
+            None => Err(self.error("unexpected end of input")),
+            Some(b'n') => self.keyword("null", Json::Null),
+            Some(b't') => self.keyword("true", Json::Bool(true)),
+            Some(b'f') => self.keyword("false", Json::Bool(false)),
+            Some(b'"') => Ok(Json::String(self.string()?)),
+            Some(b'[') => {
+                self.pos += 1;
+                let mut items = vec![];
+                if self.peek() == Some(b']') {
+                    self.pos += 1;
+                    return Ok(Json::Array(items));
+                }
+                loop {
+                    items.push(self.value()?);
+                    if self.peek() == Some(b',') { self.pos += 1; continue; }
+                    self.expect(b']')?;
+                    return Ok(Json::Array(items));
+                }
+            }
+            Some(b'{') => {
+                self.pos += 1;
+                let mut members = vec![];
+                if self.peek() == Some(b'}') {
+                    self.pos += 1;
+                    return Ok(Json::Object(members));
+                }
+                loop {
+                    if self.peek() != Some(b'"') { return Err(self.error("expected a member name")); }
+                    let key = self.string()?;
+                    self.expect(b':')?;
+                    members.push((key, self.value()?));
+                    if self.peek() == Some(b',') { self.pos += 1; continue; }
+                    self.expect(b'}')?;
+                    return Ok(Json::Object(members));
+                }
+            }
+            Some(b'-') | Some(b'0'..=b'9') => {
+                let start = self.pos;
+                self.pos += 1;
+                while self.pos < self.text.len() && self.text[self.pos].is_ascii_digit() {
+                    self.pos += 1;
+                }
+                // The slice is ASCII, so it's valid UTF-8
+                std::str::from_utf8(&self.text[start..self.pos]).unwrap().parse()
+                    .map(Json::Number)
+                    .map_err(|_| self.error("expected an integer"))
+            }
+            Some(_) => Err(self.error("unexpected character")),
+        }
+    }
+
+    /// Parse a string literal, starting at its opening quote
+    fn string(&mut self) -> Result<String, String> {
+        self.pos += 1;
+        let mut bytes = vec![];
+        loop {
+            let byte = *self.text.get(self.pos).ok_or_else(|| self.error("unterminated string"))?;
+            self.pos += 1;
+            match byte {
+                b'"' => break,
+                b'\\' => {
+                    let escape = *self.text.get(self.pos).ok_or_else(|| self.error("unterminated string"))?;
+                    self.pos += 1;
+                    let c = match escape {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => {
+                            let mut code = self.hex4()?;
+                            // A surrogate pair encodes a character outside the basic multilingual plane
+                            if (0xD800..0xDC00).contains(&code) && self.text[self.pos..].starts_with(b"\\u") {
+                                self.pos += 2;
+                                let low = self.hex4()?;
+                                code = 0x10000 + ((code - 0xD800) << 10) + (low.wrapping_sub(0xDC00) & 0x3FF);
+                            }
+                            std::char::from_u32(code).ok_or_else(|| self.error("invalid \\u escape"))?
+                        }
+                        _ => return Err(self.error("invalid escape")),
+                    };
+                    let mut buf = [0; 4];
+                    bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                }
+                byte if byte < 0x20 => return Err(self.error("control character in string")),
+                byte => bytes.push(byte),
+            }
+        }
+        String::from_utf8(bytes).map_err(|_| self.error("invalid UTF-8 in string"))
+    }
+
+    /// Parse the four hex digits of a \u escape
+    fn hex4(&mut self) -> Result<u32, String> {
+        let digits = self.text.get(self.pos..self.pos + 4).ok_or_else(|| self.error("truncated \\u escape"))?;
+        let digits = std::str::from_utf8(digits).map_err(|_| self.error("invalid \\u escape"))?;
+        let code = u32::from_str_radix(digits, 16).map_err(|_| self.error("invalid \\u escape"))?;
+        self.pos += 4;
+        Ok(code)
+    }
+}
@@ -0,0 +1,194 @@
+//! A bit-packed Game representation, for a faster search.
+//!
+//! A Game is a vector of tubes, so every successor is a heap allocation, and hashing a state hashes up to 56 bytes of Option<Ball>s.
+//! A PackedGame fits each tube into a u16, four bits per ball, so a whole game, with its Zobrist hash, is a 40 byte Copy value with no heap allocation.
+//! Four bits per ball is enough for compressed colors: a valid game has at most 13 colors, numbered from 1, and 0 is air.
+use crate::astar::{MutState, State};
+use crate::game::{self, Action, Ball, CompressedGame, Cost, Game, Tube};
+use crate::palette::Palette;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::num::NonZeroU8;
+
+/// The most tubes a PackedGame can hold, which is the most a valid game has (See: Game::validate).
+pub const MAX_TUBES: usize = 14;
+
+/// A compressed game, with each tube packed into a u16.
+///
+/// The lowest four bits of a tube are its bottom ball, and the highest four bits are its top ball.
+/// Like Tube::balls, balls fall towards the low bits; Air is 0, so an empty tube is 0.
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub struct PackedGame {
+    /// The tubes past `len` are always 0, so they don't affect equality.
+    tubes: [u16; MAX_TUBES],
+    len: u8,
+    /// The same Zobrist hash as the CompressedGame this was packed from, kept up to date by try_action.
+    zobrist: u64,
+}
+
+impl PackedGame {
+    /// Pack a compressed game.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the game has more than MAX_TUBES tubes, or a color past 15; Neither happens for a valid game.
+    pub fn new(game: &CompressedGame) -> PackedGame {
+        assert!(game.tubes.len() <= MAX_TUBES, "Can't pack a game of {} tubes", game.tubes.len());
+        let mut tubes = [0; MAX_TUBES];
+        for (packed, tube) in tubes.iter_mut().zip(&game.tubes) {
+            for (slot, ball) in tube.balls.iter().enumerate() {
+                let color = ball.map_or(0, |ball| ball.color.get());
+                assert!(color <= 0xF, "Can't pack color {:#x}", color);
+                *packed |= u16::from(color) << (4 * slot);
+            }
+        }
+        PackedGame { tubes, len: game.tubes.len() as u8, zobrist: game.zobrist().unwrap() }
+    }
+
+    /// Compress and pack a game; The Palette is the one returned by Game::compress, to unpack with.
+    pub fn from_game(game: Game) -> (PackedGame, Palette) {
+        let (compressed, palette) = game.compress();
+        (PackedGame::new(&compressed), palette)
+    }
+
+    /// Unpack into a Game, leaving the colors compressed.
+    ///
+    /// Use `CompressedGame::from(packed)` to keep the guarantee that it's compressed, or unpack to restore the original colors.
+    pub fn to_game(self) -> Game {
+        let tubes = self.tubes().iter().map(|&packed| {
+            let mut tube = Tube::empty();
+            for (slot, ball) in tube.balls.iter_mut().enumerate() {
+                *ball = NonZeroU8::new(ball_at(packed, slot)).map(|color| Ball{ color });
+            }
+            tube
+        }).collect();
+        Game { tubes }
+    }
+
+    /// Unpack into the original game, given the Palette returned by PackedGame::from_game (or Game::compress).
+    pub fn unpack(&self, palette: &Palette) -> Game {
+        CompressedGame::from(*self).decompress(palette)
+    }
+
+    /// The packed tubes (See: PackedGame)
+    pub fn tubes(&self) -> &[u16] {
+        &self.tubes[..self.len as usize]
+    }
+
+    /// Take an action, returning the resulting state, or None if the action is illegal.
+    ///
+    /// Same rules as Game::try_action, but with no allocation.
+    pub fn try_action(&self, action: Action) -> Option<PackedGame> {
+        let from = action.from as usize;
+        let to = action.to as usize;
+        let len = self.len as usize;
+        // bounds check, and NOP check
+        if from >= len || to >= len || from == to { return None; }
+        let (from_tube, to_tube) = (self.tubes[from], self.tubes[to]);
+
+        // ensure there's a spot to go and a ball to take
+        let to_idx = height(to_tube);
+        let from_idx = height(from_tube);
+        if to_idx == 4 || from_idx == 0 { return None; }
+
+        // colors must match
+        let ball = ball_at(from_tube, from_idx - 1);
+        if to_idx > 0 && ball_at(to_tube, to_idx - 1) != ball { return None; }
+
+        // cheap: it's a Copy.
+        let mut new_state = *self;
+        new_state.tubes[from] &= !(0xF << (4 * (from_idx - 1)));
+        new_state.tubes[to] |= u16::from(ball) << (4 * to_idx);
+        new_state.zobrist ^= game::zobrist_key(from, from_idx - 1, ball) ^ game::zobrist_key(to, to_idx, ball);
+        Some(new_state)
+    }
+}
+
+/// The color in `slot` of a packed tube, or 0 for air.
+pub fn ball_at(tube: u16, slot: usize) -> u8 {
+    (tube >> (4 * slot) & 0xF) as u8
+}
+
+/// The number of balls in a packed tube.
+pub fn height(tube: u16) -> usize {
+    // Balls follow gravity, so the highest nonzero nibble is the top ball
+    4 - tube.leading_zeros() as usize / 4
+}
+
+impl State for PackedGame {
+    type Edge = Action;
+    type Cost = Cost;
+    type Iter = PackedGameSuccessors;
+    fn iter_successors(self) -> PackedGameSuccessors {
+        PackedGameSuccessors {
+            state: self,
+            action: Action{ from: 0, to: 0 },
+        }
+    }
+    fn try_edge(&self, edge: &Action) -> Option<Self> {
+        self.try_action(*edge)
+    }
+    /// Every tube is empty, or four balls of one color.
+    fn is_solved(&self) -> bool {
+        self.tubes().iter().all(|&tube| tube == (tube & 0xF) * 0x1111)
+    }
+    fn zobrist(&self) -> Option<u64> {
+        Some(self.zobrist)
+    }
+}
+
+impl MutState for PackedGame {
+    /// A PackedGame is a Copy, so the board before the move is all it takes to undo it.
+    type Undo = PackedGame;
+    /// The legal actions, in the same order as PackedGameSuccessors.
+    fn edges(&self, edges: &mut Vec<(Action, Cost)>) {
+        edges.extend(self.iter_successors().map(|(_state, cost, action)| (action, cost)));
+    }
+    fn apply(&mut self, action: &Action) -> PackedGame {
+        let before = *self;
+        *self = self.try_action(*action).expect("Couldn't apply an illegal action");
+        before
+    }
+    fn undo(&mut self, before: PackedGame) {
+        *self = before;
+    }
+}
+
+// Hashes just the kept Zobrist hash, like CompressedGame's Hash impl.
+impl Hash for PackedGame {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.zobrist);
+    }
+}
+
+/// An iterator over the successive states to a packed ball game state, in the same order as GameSuccessors.
+pub struct PackedGameSuccessors {
+    state: PackedGame,
+    action: Action,
+}
+
+impl Iterator for PackedGameSuccessors {
+    type Item = (PackedGame, Cost, Action);
+    fn next(&mut self) -> Option<Self::Item> {
+        let len = self.state.len;
+        while self.action.from < len {
+            while self.action.to < len {
+                let action = self.action;
+                self.action.to += 1;
+                if let Some(new_state) = self.state.try_action(action) {
+                    // cost of all moves in ball game is 1.
+                    return Some((new_state, 1, action));
+                }
+            }
+            self.action.to = 0;
+            self.action.from += 1;
+        }
+        None
+    }
+}
+
+impl fmt::Debug for PackedGame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_game())
+    }
+}
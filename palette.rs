@@ -0,0 +1,185 @@
+//! Names for ball colors, for levels whose colors aren't single ASCII characters.
+//!
+//! Internally a ball's color is always a single non-zero byte (See: game.rs's Ball).
+//! Single ASCII graphic characters are their own color id, so levels in the original format need no palette.
+//! Any other color, such as `red` or an emoji, is given an id from 0x80 up, and the Palette remembers its name.
+//!
+//! Game::compress also returns a Palette, which names the compact ids after the original colors,
+//! so compressed games can still be displayed and written, and can be decompressed.
+use crate::game::{Ball, Game, AIR, SLASH};
+use std::collections::HashMap;
+use std::fmt;
+use std::num::NonZeroU8;
+
+/// The first id given to a named color; ids below this are ASCII characters.
+const FIRST_NAMED_ID: u8 = 0x80;
+
+/// The names of a game's named colors, kept alongside the Game.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Palette {
+    /// Name of each named color
+    names: HashMap<Ball, String>,
+    /// Reverse of `names`
+    ids: HashMap<String, Ball>,
+    /// For compressed games, the original color of each compact color id, indexed by id - 1
+    originals: Vec<Ball>,
+}
+
+impl Palette {
+    /// Whether any color has a name; if not, every color displays as its own character.
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// The ball a color token stands for, if it is known: either a named color, or a single ASCII graphic character other than `.` and `/`.
+    pub fn lookup(&self, token: &str) -> Option<Ball> {
+        if let Some(&ball) = self.ids.get(token) {
+            return Some(ball);
+        }
+        match token.as_bytes() {
+            &[byte] if byte.is_ascii_graphic() && byte != AIR && byte != SLASH => Some(Ball{ color: NonZeroU8::new(byte).unwrap() }),
+            _ => None,
+        }
+    }
+
+    /// The ball a color token stands for, naming a new color if the token hasn't been seen before.
+    /// `.` stands for an empty space, and is returned as None.
+    ///
+    /// Returns an error for unprintable ASCII characters, for `/`, and past 128 named colors.
+    pub fn color(&mut self, token: &str) -> Result<Option<Ball>, String> {
+        if token.as_bytes() == [AIR] { return Ok(None); }
+        if let Some(ball) = self.lookup(token) { return Ok(Some(ball)); }
+        if token.as_bytes() == [SLASH] {
+            return Err("'/' can't be used as a ball color, as a tube line starting with two of them would be a comment".to_string());
+        }
+        if token.len() == 1 {
+            return Err(format!("Unprintable character used as ball color. Hex {:#x}", token.as_bytes()[0]));
+        }
+        if token.starts_with("//") {
+            // A tube line starting with this name would be read as a comment
+            return Err(format!("Color names can't start with //, got {:?}", token));
+        }
+        if self.ids.len() >= (256 - FIRST_NAMED_ID as usize) {
+            return Err(format!("Too many named colors; can't name {:?}", token));
+        }
+        let ball = Ball{ color: NonZeroU8::new(FIRST_NAMED_ID + self.ids.len() as u8).unwrap() };
+        self.insert(ball, token.to_string());
+        Ok(Some(ball))
+    }
+
+    fn insert(&mut self, ball: Ball, name: String) {
+        self.names.insert(ball, name.clone());
+        self.ids.insert(name, ball);
+    }
+
+    /// Number `game`'s colors from 1, in order of first appearance, returning the Palette for the compact ids.
+    /// The compact colors are named after the original colors, using the names in `self`.
+    ///
+    /// Used by Game::compress, which wraps the result in a CompressedGame.
+    pub fn compress(&self, game: &mut Game) -> Palette {
+        let mut compressed = Palette::default();
+        // mapping from original color to new color
+        let mut compact = HashMap::new();
+        for tube in &mut game.tubes {
+            for ball in tube.balls.iter_mut().filter_map(|ball| ball.as_mut()) {
+                let original = *ball;
+                *ball = *compact.entry(original).or_insert_with(|| {
+                    compressed.originals.push(original);
+                    let ball = Ball{ color: NonZeroU8::new(compressed.originals.len() as u8).unwrap() };
+                    compressed.insert(ball, self.name(original));
+                    ball
+                });
+            }
+        }
+        compressed
+    }
+
+    /// Restore the original colors of a game compressed by Game::compress, which returned this palette.
+    /// Used by CompressedGame::decompress.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `game` has colors this palette didn't come up with.
+    pub fn decompress(&self, game: &mut Game) {
+        for tube in &mut game.tubes {
+            for ball in tube.balls.iter_mut().filter_map(|ball| ball.as_mut()) {
+                *ball = *self.originals.get(ball.color.get() as usize - 1).expect("Palette::decompress: unknown compact color");
+            }
+        }
+    }
+
+    /// The name of a ball's color: its name if it has one, otherwise the ball's usual display.
+    pub fn name(&self, ball: Ball) -> String {
+        self.names.get(&ball).cloned().unwrap_or_else(|| ball.to_string())
+    }
+
+    /// The width, in characters, of the longest color name (1 when no color is named).
+    pub fn name_width(&self) -> usize {
+        self.names.values().map(|name| name.chars().count()).max().unwrap_or(1).max(1)
+    }
+
+    /// Display a game like Game's Display impl, but with color names.
+    /// When any name is longer than a character, balls are separated by spaces and padded to the same width.
+    pub fn display<'a>(&'a self, game: &'a Game) -> Named<'a> {
+        Named { palette: self, game }
+    }
+}
+
+/// A game displayed with its palette's color names (See: Palette::display).
+pub struct Named<'a> {
+    palette: &'a Palette,
+    game: &'a Game,
+}
+
+impl<'a> fmt::Display for Named<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self.palette.name_width();
+        if width == 1 && self.palette.is_empty() {
+            return write!(f, "{}", self.game);
+        }
+        for (idx, tube) in self.game.tubes.iter().enumerate() {
+            write!(f, "[{:>2}]", idx)?;
+            for (slot, ball) in tube.balls.iter().enumerate() {
+                let name = ball.map_or(String::new(), |ball| self.palette.name(ball));
+                if slot == 0 || width > 1 { write!(f, " ")?; }
+                write!(f, "{:width$}", name, width = width)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Split a line into (approximately) its grapheme clusters, so that each user-perceived character is one ball.
+///
+/// Combining marks, variation selectors, emoji modifiers and tags stay with the character before them,
+/// characters joined by a zero width joiner stay together, and regional indicators (flags) pair up.
+/// That covers color names written as emoji or accented letters, without the full Unicode segmentation rules.
+pub fn graphemes(line: &str) -> Vec<&str> {
+    let mut clusters = vec![];
+    let mut start = 0;
+    let mut prev: Option<char> = None;
+    let mut regional_pair = false;
+    for (idx, c) in line.char_indices() {
+        let code = c as u32;
+        let extends = match code {
+            0x0300..=0x036F | 0x1AB0..=0x1AFF | 0x20D0..=0x20FF | 0xFE20..=0xFE2F // combining marks
+            | 0xFE00..=0xFE0F | 0xE0100..=0xE01EF // variation selectors
+            | 0x1F3FB..=0x1F3FF // emoji skin tone modifiers
+            | 0xE0020..=0xE007F // tags
+            | 0x200D => true, // zero width joiner
+            0x1F1E6..=0x1F1FF => regional_pair, // second regional indicator of a flag
+            _ => prev == Some('\u{200D}'),
+        };
+        if prev.is_some() && !extends {
+            clusters.push(&line[start..idx]);
+            start = idx;
+        }
+        regional_pair = (0x1F1E6..=0x1F1FF).contains(&code) && !(extends && regional_pair);
+        prev = Some(c);
+    }
+    if start < line.len() {
+        clusters.push(&line[start..]);
+    }
+    clusters
+}
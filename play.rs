@@ -0,0 +1,164 @@
+//! Interactive play mode: a human plays a level through stdin and stdout.
+use crate::game::{Action, Game};
+use crate::palette::Palette;
+use crate::astar::State;
+use crate::with_input;
+use crate::render::{RenderOptions, Renderer};
+use std::io::{self, BufRead, Write};
+
+const HELP: &str = "\
+Commands:
+  F->T or F T  move the top ball of tube F to tube T (tubes count from 0)
+  u            undo the last move
+  r            redo the last undone move
+  h            hint: show the next move of an optimal solution
+  s FILE       save the board to FILE, in the level format
+  b            show the board again
+  q            quit
+  ?            show this help";
+
+/// A game in progress, with the history needed for undo and redo.
+struct Session {
+    /// The board after each move; the first is the initial board
+    states: Vec<Game>,
+    /// The moves taken to reach each board after the first
+    moves: Vec<Action>,
+    /// Undone moves, most recently undone last
+    redo: Vec<Action>,
+    /// Length of an optimal solution from the initial board, once it has been computed
+    optimal: Option<Option<usize>>,
+    /// Names of the colors, for error messages
+    palette: Palette,
+}
+
+impl Session {
+    fn new(game: Game, palette: Palette) -> Session {
+        Session { states: vec![game], moves: vec![], redo: vec![], optimal: None, palette }
+    }
+
+    fn current(&self) -> &Game {
+        self.states.last().unwrap()
+    }
+
+    /// Take an action, explaining why if it is illegal
+    fn play(&mut self, action: Action) -> Result<(), String> {
+        let next_state = self.current().try_move(action)
+            .map_err(|e| format!("Can't move {}: {}", action, e.to_string_with_palette(&self.palette)))?;
+        self.states.push(next_state);
+        self.moves.push(action);
+        Ok(())
+    }
+
+    fn undo(&mut self) -> Option<Action> {
+        let action = self.moves.pop()?;
+        self.states.pop();
+        self.redo.push(action);
+        Some(action)
+    }
+
+    fn redo(&mut self) -> Option<Action> {
+        let action = self.redo.pop()?;
+        // Redone moves were legal when they were first played, from this same board
+        self.play(action).expect("redo of a previously legal move failed");
+        Some(action)
+    }
+
+    /// Length of an optimal solution from the initial board, solving it on first use.
+    fn optimal_len(&mut self) -> Option<usize> {
+        if self.optimal.is_none() {
+            self.optimal = Some(self.states[0].solve().map(|(path, _stats)| path.len()));
+        }
+        self.optimal.unwrap()
+    }
+}
+
+/// Parse a move, written either as "F->T" or "F T"
+fn parse_move(line: &str) -> Option<Action> {
+    Action::parse(line, false).or_else(|| {
+        let mut numbers = line.split_whitespace();
+        let from = numbers.next()?.parse().ok()?;
+        let to = numbers.next()?.parse().ok()?;
+        if numbers.next().is_some() { return None; }
+        Some(Action{ from, to })
+    })
+}
+
+/// `balls play LEVEL`
+///
+/// Plays LEVEL interactively, reading commands from stdin until the level is solved,
+/// the player quits, or stdin reaches EOF.
+pub fn main(mut args: Vec<String>) {
+    let render_options = RenderOptions::from_args(&mut args);
+    if args.len() != 1 || args[0] == "-" {
+        eprintln!("balls play: Expected one argument: input filename (stdin is used for commands)");
+        std::process::exit(1);
+    }
+    let (game, palette) = with_input(&args[0], Game::from_input_with_palette);
+    let renderer = Renderer::new(&game, &palette, &render_options);
+    let mut session = Session::new(game, palette.clone());
+
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    println!("{}\n", HELP);
+    println!("{}", renderer.render(session.current()));
+    loop {
+        print!("Move {}> ", session.moves.len() + 1);
+        io::stdout().flush().expect("Couldn't flush stdout");
+        let line = match lines.next() {
+            Some(line) => line.expect("Couldn't read command from stdin"),
+            None => { println!(); break; } // EOF
+        };
+
+        match line.trim() {
+            "" => continue,
+            "q" | "quit" => break,
+            "?" | "help" => { println!("{}", HELP); continue; }
+            "b" | "board" => {}
+            "u" | "undo" => match session.undo() {
+                Some(action) => println!("Undid {}", action),
+                None => { println!("Nothing to undo."); continue; }
+            },
+            "r" | "redo" => match session.redo() {
+                Some(action) => println!("Redid {}", action),
+                None => { println!("Nothing to redo."); continue; }
+            },
+            "h" | "hint" => {
+                match session.current().solve() {
+                    Some((path, _stats)) if path.is_empty() => println!("Already solved!"),
+                    Some((path, _stats)) => println!("Hint: {} ({} moves left in an optimal solution)", path[0], path.len()),
+                    None => println!("There's no solution from here; try undoing."),
+                }
+                continue;
+            }
+            command if command.starts_with("s ") || command.starts_with("save ") => {
+                let filename = command[command.find(' ').unwrap()..].trim();
+                match std::fs::write(filename, session.current().to_text_with_palette(&palette)) {
+                    Ok(()) => println!("Saved the board to {}", filename),
+                    Err(e) => println!("Couldn't save the board to {}: {}", filename, e),
+                }
+                continue;
+            }
+            command => match parse_move(command) {
+                Some(action) => if let Err(e) = session.play(action) {
+                    println!("{}", e);
+                    continue;
+                } else {
+                    // A new move makes the undone moves meaningless
+                    session.redo.clear();
+                },
+                None => { println!("Unknown command {:?}, enter ? for help.", command); continue; }
+            },
+        }
+        println!("{}", renderer.render_move(session.current(), session.moves.last().cloned()));
+
+        if session.current().is_solved() {
+            let taken = session.moves.len();
+            match session.optimal_len() {
+                Some(optimal) if optimal == taken => println!("Solved in {} moves, which is optimal!", taken),
+                Some(optimal) => println!("Solved in {} moves; the optimal solution takes {}.", taken, optimal),
+                None => println!("Solved in {} moves!", taken),
+            }
+            break;
+        }
+    }
+}
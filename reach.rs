@@ -0,0 +1,67 @@
+//! Reachability queries: the shortest series of moves from one board to another.
+use crate::game::{Game, Tube};
+use crate::palette::Palette;
+use crate::with_input;
+use crate::render::{RenderOptions, Renderer};
+
+/// Rewrite `game`, whose colors are named by `palette`, in the colors of `to_palette`, matching colors by name.
+///
+/// Named colors are numbered in the order they first appear in a level, so the same name can have different ids in two levels.
+fn recolor(game: &Game, palette: &Palette, to_palette: &Palette) -> Result<Game, String> {
+    let mut tubes = Vec::with_capacity(game.tubes.len());
+    for tube in &game.tubes {
+        let mut recolored = Tube::empty();
+        for (slot, ball) in tube.balls.iter().enumerate() {
+            if let Some(ball) = ball {
+                let name = palette.name(*ball);
+                recolored.balls[slot] = Some(to_palette.lookup(&name).ok_or_else(|| format!("there's no {:?} ball to move", name))?);
+            }
+        }
+        tubes.push(recolored);
+    }
+    Ok(Game { tubes })
+}
+
+/// `balls reach FROM TO`
+///
+/// Finds the shortest series of moves from the board in FROM to the board in TO, both in the level format,
+/// printing the moves (in the move list format) and the board after each.
+/// Exits with status 1 if TO can't be reached from FROM.
+pub fn main(mut args: Vec<String>) {
+    let render_options = RenderOptions::from_args(&mut args);
+    if args.len() != 2 {
+        eprintln!("balls reach: Expected two arguments: the filenames of the starting and target levels (either may be '-' for stdin)");
+        std::process::exit(1);
+    }
+    let (game, palette) = with_input(&args[0], Game::from_input_with_palette);
+    let (target, target_palette) = with_input(&args[1], Game::from_input_with_palette);
+    let target = recolor(&target, &target_palette, &palette).unwrap_or_else(|e| {
+        println!("The target can't be reached: {}", e);
+        std::process::exit(1)
+    });
+    let renderer = Renderer::new(&game, &palette, &render_options);
+    println!("Start:\n{}", renderer.render(&game));
+    println!("Target:\n{}", renderer.render(&target));
+
+    let (path, stats) = match game.solve_to(&target) {
+        Some(solution) => solution,
+        None => {
+            println!("The target can't be reached from the start.");
+            std::process::exit(1)
+        }
+    };
+    println!("{}", stats);
+    // In the move list format, so it can be saved and checked with `balls verify`, or replayed
+    println!("// {} moves from {} to {}", path.len(), args[0], args[1]);
+    for action in &path {
+        println!("{}", action);
+    }
+    println!();
+
+    let mut state = game;
+    for &action in &path {
+        state = state.try_action(action).expect("Couldn't replay action from path");
+        println!("{}", renderer.render_step(&state, action));
+    }
+    assert!(state == target, "Path did not reach the target!");
+}
@@ -0,0 +1,329 @@
+//! Terminal rendering of boards, with optional ANSI colors.
+//!
+//! Game's Display impl stays the plain ASCII form; a Renderer is used where boards are shown to people.
+use crate::game::{Action, Ball, Game, Tube};
+use crate::palette::Palette;
+use crate::take_option;
+use std::collections::{HashMap, HashSet};
+
+/// When to color the output.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ColorMode {
+    /// Color only when stdout is a terminal, `NO_COLOR` is unset, and `TERM` isn't "dumb"
+    Auto,
+    Always,
+    Never,
+}
+
+/// The set of background colors balls are drawn with.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ColorScheme {
+    /// Fourteen bright, easily named colors; enough for every color in a valid game.
+    Standard,
+    /// The eight colors of the Okabe-Ito palette, which stay distinct under common forms of colorblindness.
+    /// Colors past the eighth are told apart by symbol alone.
+    Colorblind,
+}
+
+/// Commandline options controlling how boards are rendered.
+#[derive(Debug, Copy, Clone)]
+pub struct RenderOptions {
+    pub color: ColorMode,
+    pub scheme: ColorScheme,
+    /// Draw each color as a distinct symbol, rather than its character from the level file
+    pub symbols: bool,
+    /// Draw tubes upright, as columns, rather than as rows
+    pub vertical: bool,
+    /// Number of upright tubes drawn side by side before wrapping onto another row of tubes
+    pub wrap: usize,
+}
+
+/// 256-color terminal codes for ColorScheme::Standard:
+/// red, blue, yellow, green, magenta, cyan, orange, purple, white, brown, pink, grey, navy, dark green
+const STANDARD: [u8; 14] = [196, 21, 226, 34, 201, 51, 208, 93, 255, 94, 218, 244, 17, 22];
+/// 256-color approximations of the Okabe-Ito palette:
+/// orange, sky blue, bluish green, yellow, blue, vermillion, reddish purple, black
+const COLORBLIND: [u8; 8] = [214, 74, 29, 227, 25, 166, 175, 16];
+/// Well-known color letters and names, with the slot of the matching color in STANDARD and in COLORBLIND, if there is one.
+/// Letters and names are matched ignoring case.
+const KNOWN_COLORS: [(&[&str], Option<usize>, Option<usize>); 14] = [
+    (&["r", "red"], Some(0), Some(5)),
+    (&["b", "blue"], Some(1), Some(4)),
+    (&["y", "yellow"], Some(2), Some(3)),
+    (&["g", "green"], Some(3), Some(2)),
+    (&["m", "magenta"], Some(4), Some(6)),
+    (&["c", "cyan"], Some(5), Some(1)),
+    (&["o", "orange"], Some(6), Some(0)),
+    (&["p", "purple"], Some(7), Some(6)),
+    (&["w", "white"], Some(8), None),
+    (&["n", "brown"], Some(9), None),
+    (&["pink"], Some(10), None),
+    (&["grey", "gray"], Some(11), None),
+    (&["navy"], Some(12), None),
+    (&["k", "black"], None, Some(7)),
+];
+/// Symbols for the symbol fallback, one per color slot.
+const SYMBOLS: [char; 14] = ['●', '▲', '■', '◆', '★', '♥', '♣', '♠', '✚', '▼', '◐', '◇', '○', '□'];
+
+impl RenderOptions {
+    /// Remove the rendering options from `args`:
+    /// `--color auto|always|never`, `--scheme standard|colorblind`, `--symbols`, `--vertical`, and `--wrap N`.
+    ///
+    /// Exits with an error message on unrecognized values.
+    pub fn from_args(args: &mut Vec<String>) -> RenderOptions {
+        let color = match take_option(args, "--color").as_deref() {
+            None | Some("auto") => ColorMode::Auto,
+            Some("always") => ColorMode::Always,
+            Some("never") => ColorMode::Never,
+            Some(other) => {
+                eprintln!("balls: --color must be auto, always, or never, not {:?}", other);
+                std::process::exit(1)
+            }
+        };
+        let scheme = match take_option(args, "--scheme").as_deref() {
+            None | Some("standard") => ColorScheme::Standard,
+            Some("colorblind") => ColorScheme::Colorblind,
+            Some(other) => {
+                eprintln!("balls: --scheme must be standard or colorblind, not {:?}", other);
+                std::process::exit(1)
+            }
+        };
+        let symbols = crate::take_switch(args, "--symbols");
+        let vertical = crate::take_switch(args, "--vertical");
+        let wrap = take_option(args, "--wrap").map(|wrap| match wrap.parse() {
+            Ok(wrap) if wrap > 0 => wrap,
+            _ => {
+                eprintln!("balls: --wrap must be a positive number of tubes, not {:?}", wrap);
+                std::process::exit(1)
+            }
+        }).unwrap_or(7);
+        RenderOptions { color, scheme, symbols, vertical, wrap }
+    }
+}
+
+/// Draws boards for the terminal.
+pub struct Renderer {
+    /// Whether to emit ANSI color codes
+    color: bool,
+    scheme: ColorScheme,
+    symbols: bool,
+    vertical: bool,
+    wrap: usize,
+    /// Color slot of each ball color on the initial board: the matching color for well-known colors (See: KNOWN_COLORS),
+    /// and the remaining slots for the rest, in order of first appearance.
+    /// Keeping this fixed keeps each ball's color the same from one board to the next.
+    slots: HashMap<Ball, usize>,
+    /// Names of the colors
+    palette: Palette,
+    /// Width of a ball's label, in characters
+    label_width: usize,
+}
+
+impl Renderer {
+    /// Create a renderer for `game` and the boards that follow it, labelling balls with the names in `palette`.
+    pub fn new(game: &Game, palette: &Palette, options: &RenderOptions) -> Renderer {
+        let color = match options.color {
+            ColorMode::Always => true,
+            ColorMode::Never => false,
+            ColorMode::Auto => stdout_is_tty()
+                && std::env::var_os("NO_COLOR").is_none()
+                && std::env::var("TERM").map(|term| term != "dumb").unwrap_or(true),
+        };
+        let balls: Vec<Ball> = game.tubes.iter().flat_map(|tube| tube.balls.iter().filter_map(|&ball| ball)).collect();
+        // Well-known colors first, so that `R` is drawn on red; If two colors match the same slot, the first one gets it.
+        let mut slots = HashMap::new();
+        let mut taken = HashSet::new();
+        for &ball in &balls {
+            if slots.contains_key(&ball) { continue; }
+            if let Some(slot) = known_slot(&palette.name(ball), options.scheme) {
+                if taken.insert(slot) { slots.insert(ball, slot); }
+            }
+        }
+        let mut free = (0..).filter(|slot| !taken.contains(slot));
+        for &ball in &balls {
+            slots.entry(ball).or_insert_with(|| free.next().unwrap());
+        }
+        Renderer {
+            color,
+            scheme: options.scheme,
+            symbols: options.symbols,
+            vertical: options.vertical,
+            wrap: options.wrap,
+            slots,
+            palette: palette.clone(),
+            label_width: if options.symbols { 1 } else { palette.name_width() },
+        }
+    }
+
+    /// Draw a board.
+    ///
+    /// With colors, symbols, and upright tubes all off, this is exactly the palette's display of the game,
+    /// which for games without named colors is Game's Display output.
+    pub fn render(&self, game: &Game) -> String {
+        self.render_move(game, None)
+    }
+
+    /// Draw a board of a listed solution, after `action`.
+    ///
+    /// The move is highlighted only when colors, symbols, or upright tubes are on,
+    /// so plain output stays exactly the palette's display of each board.
+    pub fn render_step(&self, game: &Game, action: Action) -> String {
+        let plain = !self.color && !self.symbols && !self.vertical;
+        self.render_move(game, if plain { None } else { Some(action) })
+    }
+
+    /// Draw a board, highlighting the source and destination tubes of `last_move`, the move that led to it.
+    pub fn render_move(&self, game: &Game, last_move: Option<Action>) -> String {
+        if self.vertical {
+            return self.render_vertical(game, last_move);
+        }
+        if !self.color && !self.symbols && last_move.is_none() {
+            return self.palette.display(game).to_string();
+        }
+        let mut out = String::new();
+        for (idx, tube) in game.tubes.iter().enumerate() {
+            out += &format!("[{:>2}]", idx);
+            for (slot, ball) in tube.balls.iter().enumerate() {
+                // Single characters are written side by side, but longer names need separating
+                if slot == 0 || (self.label_width > 1 && !self.color) { out += " "; }
+                out += &self.render_slot(*ball);
+            }
+            match last_move {
+                Some(action) if action.from as usize == idx => out += &self.highlight(" <- from"),
+                Some(action) if action.to as usize == idx => out += &self.highlight(" <- to"),
+                _ => {}
+            }
+            out += "\n";
+        }
+        out
+    }
+
+    /// Draw the tubes upright in rows of at most `wrap` tubes, bottom ball at the bottom,
+    /// with each tube's index underneath it.
+    fn render_vertical(&self, game: &Game, last_move: Option<Action>) -> String {
+        // The width of one tube: a wall either side of a ball
+        let width = self.label_width + if self.color { 4 } else { 2 };
+        let mut out = String::new();
+        for (row_idx, row) in game.tubes.chunks(self.wrap).enumerate() {
+            if row_idx > 0 { out += "\n"; }
+            let first_idx = row_idx * self.wrap;
+            // top ball first
+            for slot in (0..4).rev() {
+                let cells: Vec<String> = row.iter()
+                    .map(|tube: &Tube| format!("|{}|", self.render_slot(tube.balls[slot])))
+                    .collect();
+                out += cells.join(" ").trim_end();
+                out += "\n";
+            }
+            let labels: Vec<String> = (first_idx..first_idx + row.len())
+                .map(|idx| {
+                    let label = format!("{:^width$}", idx, width = width);
+                    match last_move {
+                        Some(action) if action.from as usize == idx || action.to as usize == idx => self.highlight(&label),
+                        _ => label,
+                    }
+                })
+                .collect();
+            out += labels.join(" ").trim_end();
+            out += "\n";
+            if let Some(action) = last_move {
+                let markers: Vec<String> = (first_idx..first_idx + row.len())
+                    .map(|idx| {
+                        let marker = if action.from as usize == idx { "from" } else if action.to as usize == idx { "to" } else { "" };
+                        format!("{:^width$}", marker, width = width)
+                    })
+                    .collect();
+                let markers = markers.join(" ");
+                if !markers.trim().is_empty() {
+                    out += markers.trim_end();
+                    out += "\n";
+                }
+            }
+        }
+        out
+    }
+
+    /// Draw a ball, or an empty space the same width as a ball.
+    fn render_slot(&self, ball: Option<Ball>) -> String {
+        match ball {
+            Some(ball) => self.render_ball(ball),
+            None => " ".repeat(self.label_width + if self.color { 2 } else { 0 }),
+        }
+    }
+
+    /// Emphasize text in reverse video, if colors are on.
+    fn highlight(&self, text: &str) -> String {
+        if self.color {
+            format!("\x1b[7m{}\x1b[0m", text)
+        } else {
+            text.to_string()
+        }
+    }
+
+    /// Draw a single ball: its label on a colored background with a space either side, or just its label without color.
+    fn render_ball(&self, ball: Ball) -> String {
+        // Balls that weren't on the initial board fall back to a slot picked by their color
+        let slot = self.slots.get(&ball).cloned().unwrap_or(ball.color.get() as usize);
+        let backgrounds: &[u8] = match self.scheme {
+            ColorScheme::Standard => &STANDARD,
+            ColorScheme::Colorblind => &COLORBLIND,
+        };
+        let background = backgrounds.get(slot);
+        // Past the end of the palette, a symbol is the only way to tell the colors apart
+        let label = if self.symbols || (self.color && background.is_none()) {
+            SYMBOLS[slot % SYMBOLS.len()].to_string()
+        } else {
+            self.palette.name(ball)
+        };
+        let label = if self.vertical {
+            format!("{:^width$}", label, width = self.label_width)
+        } else {
+            format!("{:width$}", label, width = self.label_width)
+        };
+        match background {
+            Some(&background) if self.color =>
+                format!("\x1b[48;5;{};38;5;{}m {} \x1b[0m", background, foreground(background), label),
+            _ if self.color => format!(" {} ", label),
+            _ => label,
+        }
+    }
+}
+
+/// The slot of the color a well-known color letter or name stands for in `scheme`, if it has one.
+fn known_slot(name: &str, scheme: ColorScheme) -> Option<usize> {
+    let name = name.to_ascii_lowercase();
+    let &(_, standard, colorblind) = KNOWN_COLORS.iter().find(|(names, _, _)| names.contains(&name.as_str()))?;
+    match scheme {
+        ColorScheme::Standard => standard,
+        ColorScheme::Colorblind => colorblind,
+    }
+}
+
+/// Black or white text, whichever reads better on the 256-color `background`.
+fn foreground(background: u8) -> u8 {
+    let (r, g, b) = match background {
+        16..=231 => {
+            let cube = background - 16;
+            (cube / 36, cube / 6 % 6, cube % 6)
+        }
+        // greyscale ramp, and the 16 system colors treated as mid-grey
+        232..=255 => { let level = (background - 232) / 4; (level, level, level) }
+        _ => (3, 3, 3),
+    };
+    // Rough perceived brightness, out of 5
+    if u32::from(r) * 3 + u32::from(g) * 6 + u32::from(b) >= 25 { 16 } else { 231 }
+}
+
+/// Whether stdout is a terminal.
+#[cfg(unix)]
+pub fn stdout_is_tty() -> bool {
+    // libc is always linked on unix, so there's no need for a crate to reach isatty.
+    extern "C" { fn isatty(fd: i32) -> i32; }
+    unsafe { isatty(1) == 1 }
+}
+
+/// Whether stdout is a terminal; Always assumed not to be on other platforms.
+#[cfg(not(unix))]
+pub fn stdout_is_tty() -> bool {
+    false
+}
@@ -0,0 +1,125 @@
+//! Replay of a solution, either animated or stepped through interactively.
+use crate::game::{Action, Game};
+use crate::render::{self, RenderOptions, Renderer};
+use crate::verify::MoveList;
+use crate::{take_option, take_switch, with_input};
+use std::io::{self, BufRead, Write};
+use std::time::Duration;
+
+const HELP: &str = "\
+Commands:
+  n or Enter  step forward
+  p           step back
+  N           jump to step N (0 is the initial board)
+  q           quit
+  ?           show this help";
+
+/// The boards of a solution, from the initial board to the final board.
+struct Replay {
+    /// The board after each move; the first is the initial board
+    states: Vec<Game>,
+    /// The moves leading to each board after the first
+    moves: Vec<Action>,
+}
+
+impl Replay {
+    /// Draw step `step`: the action that led to it, the moves remaining, and the board.
+    fn frame(&self, renderer: &Renderer, step: usize) -> String {
+        let total = self.moves.len();
+        let (heading, last_move) = if step == 0 {
+            (format!("Step 0 of {}: initial board", total), None)
+        } else {
+            let action = self.moves[step - 1];
+            (format!("Step {} of {}: {:?}", step, total, action), Some(action))
+        };
+        format!("{}, {} moves remaining\n{}", heading, total - step, renderer.render_move(&self.states[step], last_move))
+    }
+}
+
+/// `balls replay LEVEL [MOVES] [--delay MS] [--step]`
+///
+/// Replays MOVES (a move list, See: verify.rs) onto LEVEL, or the agent's solution if MOVES is omitted.
+/// By default the boards are animated `--delay` milliseconds apart;
+/// `--step` instead reads commands to step through the boards from stdin.
+pub fn main(mut args: Vec<String>) {
+    let render_options = RenderOptions::from_args(&mut args);
+    let step = take_switch(&mut args, "--step");
+    let delay = take_option(&mut args, "--delay").map(|delay| delay.parse().unwrap_or_else(|_| {
+        eprintln!("balls replay: --delay must be a number of milliseconds, not {:?}", delay);
+        std::process::exit(1)
+    })).unwrap_or(500);
+    if args.is_empty() || args.len() > 2 {
+        eprintln!("balls replay: Expected a level filename, and optionally a move list filename");
+        std::process::exit(1);
+    }
+
+    let (game, palette) = with_input(&args[0], Game::from_input_with_palette);
+    let moves = match args.get(1) {
+        Some(filename) => with_input(filename, MoveList::from_input).unwrap_or_else(|e| {
+            eprintln!("balls replay: {}", e);
+            std::process::exit(1)
+        }).actions,
+        None => game.solve().expect("Couldn't solve ball game").0,
+    };
+
+    let mut states = vec![game];
+    for (idx, &action) in moves.iter().enumerate() {
+        match states.last().unwrap().try_move(action) {
+            Ok(next_state) => states.push(next_state),
+            Err(e) => {
+                eprintln!("balls replay: Move {} ({}) is illegal: {}", idx + 1, action, e.to_string_with_palette(&palette));
+                std::process::exit(1);
+            }
+        }
+    }
+    let replay = Replay { states, moves };
+    let renderer = Renderer::new(&replay.states[0], &palette, &render_options);
+
+    if step {
+        stepper(&replay, &renderer);
+    } else {
+        animate(&replay, &renderer, Duration::from_millis(delay));
+    }
+}
+
+/// Show every step in turn, redrawing over the previous frame when stdout is a terminal.
+fn animate(replay: &Replay, renderer: &Renderer, delay: Duration) {
+    let clear = render::stdout_is_tty();
+    for step in 0..replay.states.len() {
+        if step > 0 {
+            std::thread::sleep(delay);
+        }
+        if clear {
+            // Clear the screen and move the cursor to the top left
+            print!("\x1b[2J\x1b[H");
+        }
+        println!("{}", replay.frame(renderer, step));
+        io::stdout().flush().expect("Couldn't flush stdout");
+    }
+}
+
+/// Step through the replay with commands from stdin.
+fn stepper(replay: &Replay, renderer: &Renderer) {
+    let last = replay.moves.len();
+    let mut step = 0;
+    println!("{}\n", HELP);
+    println!("{}", replay.frame(renderer, step));
+
+    let stdin = io::stdin();
+    for line in stdin.lock().lines() {
+        let line = line.expect("Couldn't read command from stdin");
+        let next_step = match line.trim() {
+            "" | "n" => if step < last { step + 1 } else { println!("Already at the last step."); continue },
+            "p" => if step > 0 { step - 1 } else { println!("Already at the initial board."); continue },
+            "q" => break,
+            "?" => { println!("{}", HELP); continue }
+            command => match command.parse::<usize>() {
+                Ok(n) if n <= last => n,
+                Ok(n) => { println!("There is no step {}; the last step is {}.", n, last); continue }
+                Err(_) => { println!("Unknown command {:?}, enter ? for help.", command); continue }
+            },
+        };
+        step = next_step;
+        println!("{}", replay.frame(renderer, step));
+    }
+}
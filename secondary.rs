@@ -0,0 +1,123 @@
+//! Secondary objectives: of the shortest solutions, find the one that's easiest for a person to follow.
+//!
+//! Each move costs Lex(1, secondary cost), so A* finds a shortest solution, and of those, the one of least secondary cost.
+use crate::astar::{self, Lex, Solution, SolveOptions, State};
+use crate::game::{Action, CompressedGame, Cost, Tube};
+use crate::h10s;
+use std::hash::{Hash, Hasher};
+
+/// What to minimize among the shortest solutions.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Objective {
+    /// Moves onto an empty tube
+    EmptyTubeUse,
+    /// Tubes holding more than one color, counted after every move; So the fewer and briefer the mixed tubes, the better.
+    DirtyTubes,
+    /// Tubes the solution touches at all, as the source or destination of a move
+    DistinctTubes,
+}
+
+impl Objective {
+    /// The cost of taking `action` from `game`, which leads to `next`.
+    fn cost(self, game: &SecondaryGame, action: Action, next: &SecondaryGame) -> Cost {
+        match self {
+            Objective::EmptyTubeUse => if game.game.tubes[action.to as usize].balls[0].is_none() { 1 } else { 0 },
+            Objective::DirtyTubes => next.game.tubes.iter().filter(|tube| is_dirty(tube)).count() as Cost,
+            Objective::DistinctTubes => (next.touched.count_ones() - game.touched.count_ones()) as Cost,
+        }
+    }
+}
+
+/// Whether a tube holds balls of more than one color.
+fn is_dirty(tube: &Tube) -> bool {
+    tube.balls.iter().filter_map(|&ball| ball).any(|ball| Some(ball) != tube.balls[0])
+}
+
+/// A compressed game whose moves cost Lex(1, the objective's cost).
+#[derive(Clone, Debug)]
+pub struct SecondaryGame {
+    pub game: CompressedGame,
+    objective: Objective,
+    /// The tubes touched so far, as a bitmask; Only kept for Objective::DistinctTubes, so other objectives don't tell states apart by it.
+    touched: u16,
+}
+
+// Only the game and the touched tubes matter, as every SecondaryGame in a search has the same objective.
+impl PartialEq for SecondaryGame {
+    fn eq(&self, other: &Self) -> bool {
+        self.touched == other.touched && self.game == other.game
+    }
+}
+
+impl Eq for SecondaryGame {}
+
+impl Hash for SecondaryGame {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.zobrist().unwrap());
+    }
+}
+
+impl State for SecondaryGame {
+    type Edge = Action;
+    type Cost = Lex<Cost, Cost>;
+    type Iter = std::vec::IntoIter<(SecondaryGame, Lex<Cost, Cost>, Action)>;
+    fn iter_successors(self) -> Self::Iter {
+        let successors: Vec<_> = self.game.clone().iter_successors().map(|(game, moves, action)| {
+            let next = self.next(game, action);
+            let cost = Lex(moves, self.objective.cost(&self, action, &next));
+            (next, cost, action)
+        }).collect();
+        successors.into_iter()
+    }
+    fn try_edge(&self, edge: &Action) -> Option<Self> {
+        self.game.try_action(*edge).map(|game| self.next(game, *edge))
+    }
+    fn is_solved(&self) -> bool {
+        self.game.is_solved()
+    }
+    /// The game's Zobrist hash, mixed with the touched tubes.
+    fn zobrist(&self) -> Option<u64> {
+        Some(self.game.zobrist().unwrap() ^ u64::from(self.touched).wrapping_mul(0x9E37_79B9_7F4A_7C15))
+    }
+}
+
+impl SecondaryGame {
+    pub fn new(game: CompressedGame, objective: Objective) -> SecondaryGame {
+        assert!(game.tubes.len() <= 16, "Can't keep track of the tubes touched in a game of {} tubes", game.tubes.len());
+        SecondaryGame { game, objective, touched: 0 }
+    }
+
+    /// The state after `action` took this state's game to `game`.
+    fn next(&self, game: CompressedGame, action: Action) -> SecondaryGame {
+        let touched = if self.objective == Objective::DistinctTubes {
+            self.touched | 1 << action.from | 1 << action.to
+        } else {
+            0
+        };
+        SecondaryGame { game, objective: self.objective, touched }
+    }
+}
+
+/// Find the shortest solution to `game`, and of those, the one that's best by `objective`.
+///
+/// The heuristic is compressed_dig_clutter for the moves, and nothing for the objective.
+pub fn solve(game: CompressedGame, objective: Objective, options: &SolveOptions) -> Option<Solution<SecondaryGame>> {
+    let heuristic = |state: &SecondaryGame| Lex(h10s::compressed_dig_clutter(&state.game), 0);
+    astar::solve_with(SecondaryGame::new(game, objective), heuristic, options)
+}
+
+/// The cost of `path` from `game` by `objective`, for comparing with other solutions.
+///
+/// # Panics
+///
+/// Panics if the path has an illegal move.
+pub fn path_cost(game: CompressedGame, objective: Objective, path: &[Action]) -> Cost {
+    let mut state = SecondaryGame::new(game, objective);
+    let mut cost = 0;
+    for &action in path {
+        let next = state.try_edge(&action).expect("Couldn't take an illegal move");
+        cost += objective.cost(&state, action, &next);
+        state = next;
+    }
+    cost
+}
@@ -0,0 +1,618 @@
+//! Property checks over randomly generated games, run with `balls selfcheck` (or `make check`).
+//!
+//! Each property is checked against every level named on the commandline, and against random games:
+//! shuffled levels of random colors, played a random number of random moves into the game.
+use crate::game::{self, Action, Ball, CompressedGame, Game, Goal, MoveError, Tube, AIR, SLASH};
+use crate::astar::{self, FloatCost, MutState, SolveOptions, State, TieBreak, WorkQueueKind};
+use crate::h10s;
+use crate::json::{self, Json};
+use crate::packed::PackedGame;
+use crate::weighted::{self, CostModel, Metric};
+use crate::secondary::{self, Objective};
+use crate::palette::Palette;
+use crate::{take_option, with_input};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::num::NonZeroU8;
+use std::panic::{self, AssertUnwindSafe};
+
+/// A small, seedable pseudo-random number generator (xorshift64*).
+/// Good enough for generating test cases, and keeps balls free of dependencies.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        // xorshift gets stuck at zero
+        Rng(seed ^ 0x9E37_79B9_7F4A_7C15)
+    }
+
+    pub fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// A number in the range 0..n
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for idx in (1..items.len()).rev() {
+            items.swap(idx, self.below(idx + 1));
+        }
+    }
+}
+
+/// A random valid game: 2 to 10 colors, 1 to 3 empty tubes, shuffled, then played up to 40 random moves.
+pub fn random_game(rng: &mut Rng) -> Game {
+    let color_count = 2 + rng.below(9);
+    let empty_count = 1 + rng.below(3);
+
+    // Any ASCII graphic character except AIR and SLASH; the first few are the ones most likely to need escaping
+    let mut colors: Vec<u8> = (0x21..=0x7E).filter(|&color| color != AIR && color != SLASH).collect();
+    rng.shuffle(&mut colors[3..]);
+    rng.shuffle(&mut colors[..color_count]);
+    let mut balls: Vec<Ball> = colors[..color_count].iter()
+        .flat_map(|&color| vec![Ball{ color: NonZeroU8::new(color).unwrap() }; 4])
+        .collect();
+    rng.shuffle(&mut balls);
+
+    let mut tubes: Vec<Tube> = balls.chunks(4)
+        .map(|chunk| Tube{ balls: [Some(chunk[0]), Some(chunk[1]), Some(chunk[2]), Some(chunk[3])] })
+        .collect();
+    tubes.extend((0..empty_count).map(|_| Tube::empty()));
+    rng.shuffle(&mut tubes);
+
+    let mut game = Game { tubes };
+    for _ in 0..rng.below(41) {
+        let successors: Vec<Game> = game.clone().iter_successors().map(|(state, _, _)| state).collect();
+        if successors.is_empty() { break; }
+        let idx = rng.below(successors.len());
+        game = successors.into_iter().nth(idx).unwrap();
+    }
+    game
+}
+
+/// Reading back a game written in the level format gives the same game.
+fn text_round_trip(game: &Game) -> Result<(), String> {
+    let text = game.to_text();
+    let parsed = Game::from_input(&mut text.as_bytes());
+    if parsed != *game {
+        return Err(format!("read back as:\n{}", parsed));
+    }
+    Ok(())
+}
+
+/// Short tube lines read the same as tube lines padded with empty spaces.
+fn short_line_round_trip(game: &Game) -> Result<(), String> {
+    let text: Vec<String> = game.to_text().lines().enumerate().map(|(idx, line)| {
+        let short = line.trim_end_matches(AIR as char);
+        // Keep the two header lines, and lines that would become blank or a comment
+        if idx < 2 || short.is_empty() || short.starts_with("//") { line.to_string() } else { short.to_string() }
+    }).collect();
+    let parsed = Game::from_input(&mut text.join("\n").as_bytes());
+    if parsed != *game {
+        return Err(format!("read back as:\n{}", parsed));
+    }
+    Ok(())
+}
+
+/// Reading back a game written as JSON gives the same game.
+fn json_round_trip(game: &Game) -> Result<(), String> {
+    let text = json::game(game, &Palette::default()).to_string();
+    let (parsed, _palette) = Json::parse(&text).and_then(|json| json::to_game(&json)).map_err(|e| format!("{} in {}", e, text))?;
+    if parsed != *game {
+        return Err(format!("{} read back as:\n{}", text, parsed));
+    }
+    Ok(())
+}
+
+/// Compressing a game and decompressing it gives back the same game,
+/// and the compressed game displays and writes the same as the original.
+fn compress_round_trip(game: &Game) -> Result<(), String> {
+    let (compressed, palette) = game.clone().compress();
+    if palette.display(&compressed).to_string() != game.to_string() {
+        return Err(format!("compressed game displayed as:\n{}", palette.display(&compressed)));
+    }
+    let text = compressed.to_text_with_palette(&palette);
+    if text != game.to_text() {
+        return Err(format!("compressed game written as:\n{}", text));
+    }
+    let decompressed = compressed.decompress(&palette);
+    if decompressed != *game {
+        return Err(format!("decompressed as:\n{}", decompressed));
+    }
+    Ok(())
+}
+
+/// Compressing a game with named colors keeps the names.
+fn named_compress_round_trip(game: &Game) -> Result<(), String> {
+    let (named, names) = named_game(game);
+    let (compressed, palette) = named.clone().compress_with_palette(&names);
+    let (expected, got) = (names.display(&named).to_string(), palette.display(&compressed).to_string());
+    if expected != got {
+        return Err(format!("{}\ncompressed game displayed as:\n{}", expected, got));
+    }
+    let decompressed = compressed.decompress(&palette);
+    if decompressed != named {
+        return Err(format!("decompressed as:\n{}", names.display(&decompressed)));
+    }
+    Ok(())
+}
+
+/// Packing a compressed game and unpacking it gives the same game,
+/// and the packed game has the same successors (in the same order), solvedness and heuristic as the compressed game.
+fn packed_round_trip(game: &Game) -> Result<(), String> {
+    let (compressed, palette) = game.clone().compress();
+    let packed = PackedGame::new(&compressed);
+    if PackedGame::from_game(game.clone()).0 != packed {
+        return Err("PackedGame::from_game packed differently to PackedGame::new".to_string());
+    }
+    if packed.unpack(&palette) != *game {
+        return Err(format!("unpacked as:\n{}", packed.unpack(&palette)));
+    }
+    if packed.is_solved() != compressed.is_solved() {
+        return Err(format!("packed game is_solved: {}", packed.is_solved()));
+    }
+    let (expected, got) = (h10s::compressed_dig_clutter(&compressed), h10s::packed_dig_clutter(&packed));
+    if expected != got {
+        return Err(format!("packed_dig_clutter gave {}, not {}", got, expected));
+    }
+    let expected: Vec<_> = compressed.iter_successors().map(|(state, cost, action)| (PackedGame::new(&state), cost, action)).collect();
+    let got: Vec<_> = packed.iter_successors().collect();
+    if expected != got {
+        return Err(format!("packed successors:\n{:?}\nnot:\n{:?}", got, expected));
+    }
+    Ok(())
+}
+
+/// The Zobrist hashes kept up to date by CompressedGame::try_action and PackedGame::try_action
+/// match a hash of the whole game, along a random walk from `game`.
+fn zobrist_incremental(game: &Game) -> Result<(), String> {
+    let (mut compressed, _palette) = game.clone().compress();
+    let mut packed = PackedGame::new(&compressed);
+    let mut rng = Rng::new(compressed.zobrist().unwrap());
+    for step in 0..20 {
+        let full = game::zobrist_hash(&compressed);
+        if compressed.zobrist() != Some(full) || packed.zobrist() != Some(full) {
+            return Err(format!("after {} moves, to\n{}hashes were {:?} (compressed) and {:?} (packed), not {}",
+                step, *compressed, compressed.zobrist(), packed.zobrist(), full));
+        }
+        let successors: Vec<_> = compressed.clone().iter_successors().collect();
+        if successors.is_empty() { break; }
+        let (next, _cost, action) = successors[rng.below(successors.len())].clone();
+        compressed = next;
+        packed = packed.try_action(action).ok_or_else(|| format!("packed game couldn't take {}", action))?;
+    }
+    Ok(())
+}
+
+/// Walking a game with MutState's apply and undo gives the same states as its successors,
+/// for Games, CompressedGames (including their Zobrist hashes) and PackedGames.
+fn apply_undo(game: &Game) -> Result<(), String> {
+    let mut rng = Rng::new(game::zobrist_hash(game));
+    apply_undo_walk(game.clone(), &mut rng)?;
+    let (compressed, _palette) = game.clone().compress();
+    apply_undo_walk(PackedGame::new(&compressed), &mut rng)?;
+    apply_undo_walk(compressed, &mut rng)
+}
+
+/// Check apply and undo against iter_successors along a random walk from `state`.
+fn apply_undo_walk<S: MutState<Edge = Action>>(mut state: S, rng: &mut Rng) -> Result<(), String> {
+    let mut history = vec![];
+    for _ in 0..20 {
+        let successors: Vec<_> = state.clone().iter_successors().collect();
+        let mut edges = vec![];
+        state.edges(&mut edges);
+        let expected: Vec<_> = successors.iter().map(|(_, cost, action)| (*action, *cost)).collect();
+        if edges != expected {
+            return Err(format!("edges of\n{:?}\nwere {:?}, not {:?}", state, edges, expected));
+        }
+        for (successor, _cost, action) in &successors {
+            let before = state.clone();
+            let undo = state.apply(action);
+            if state != *successor || state.zobrist() != successor.zobrist() {
+                return Err(format!("applying {} to\n{:?}\ngave\n{:?}\nnot\n{:?}", action, before, state, successor));
+            }
+            state.undo(undo);
+            if state != before || state.zobrist() != before.zobrist() {
+                return Err(format!("undoing {} gave\n{:?}\nnot\n{:?}", action, state, before));
+            }
+        }
+        if successors.is_empty() { break; }
+        let (_, _, action) = successors[rng.below(successors.len())];
+        history.push((state.clone(), state.apply(&action)));
+    }
+    // Undo the whole walk, back to the start
+    while let Some((before, undo)) = history.pop() {
+        state.undo(undo);
+        if state != before {
+            return Err(format!("undoing the walk gave\n{:?}\nnot\n{:?}", state, before));
+        }
+    }
+    Ok(())
+}
+
+/// The state after taking each move of `path` from `state`, or a description of the first illegal move.
+fn replay<S: State<Edge = Action>>(mut state: S, path: &[Action]) -> Result<S, String> {
+    for action in path {
+        state = state.try_edge(action).ok_or_else(|| format!("couldn't replay {} from {:?}", action, path))?;
+    }
+    Ok(state)
+}
+
+/// A* finds solutions of the same length with either work queue and any tie-breaking policy, and so does IDA*.
+/// For the same policy, both work queues pop nodes in the same order, so they find the same solution.
+fn work_queues_agree(game: &Game) -> Result<(), String> {
+    let (compressed, _palette) = game.clone().compress();
+    let ida = astar::ida_solve(compressed.clone(), h10s::compressed_dig_clutter).map(|(path, _stats)| path.len());
+    for &tie_break in &[TieBreak::Fifo, TieBreak::Lifo, TieBreak::HighCost, TieBreak::LowCost] {
+        let solve = |work_queue| {
+            let options = SolveOptions{ work_queue, tie_break };
+            astar::solve_with(compressed.clone(), h10s::compressed_dig_clutter, &options)
+                .map(|(path, stats)| (path, stats.visited_len))
+        };
+        let (heap, buckets) = (solve(WorkQueueKind::BinaryHeap), solve(WorkQueueKind::Buckets));
+        if heap != buckets {
+            return Err(format!("with {:?} tie-breaking, the binary heap found {:?}, but buckets found {:?}", tie_break, heap, buckets));
+        }
+        let len = heap.map(|(path, _visited_len)| path.len());
+        if len != ida {
+            return Err(format!("solution lengths were {:?} with {:?} tie-breaking, and {:?} with IDA*", len, tie_break, ida));
+        }
+    }
+    Ok(())
+}
+
+/// A ball game in which every move costs half as much, to check the search with fractional costs.
+#[derive(Clone, Hash, PartialEq, Eq, Debug)]
+struct HalfCost(CompressedGame);
+
+impl State for HalfCost {
+    type Edge = Action;
+    type Cost = FloatCost;
+    type Iter = Box<dyn Iterator<Item = (HalfCost, FloatCost, Action)>>;
+    fn iter_successors(self) -> Self::Iter {
+        Box::new(self.0.iter_successors().map(|(state, cost, action)| (HalfCost(state), FloatCost(f64::from(cost) / 2.0), action)))
+    }
+    fn try_edge(&self, edge: &Action) -> Option<Self> {
+        self.0.try_action(*edge).map(HalfCost)
+    }
+    fn is_solved(&self) -> bool {
+        self.0.is_solved()
+    }
+}
+
+/// Searching with FloatCost costs finds a solution as long as with integer costs, costing half as much.
+fn float_costs(game: &Game) -> Result<(), String> {
+    let (compressed, _palette) = game.clone().compress();
+    let integer = astar::solve(compressed.clone(), h10s::compressed_dig_clutter)
+        .map(|(path, stats)| (path.len(), f64::from(stats.path_cost) / 2.0));
+    let float = astar::solve(HalfCost(compressed), |state: &HalfCost| FloatCost(f64::from(h10s::compressed_dig_clutter(&state.0)) / 2.0))
+        .map(|(path, stats)| (path.len(), stats.path_cost.0));
+    if integer != float {
+        return Err(format!("found (length, cost) {:?} with halved float costs, not {:?}", float, integer));
+    }
+    Ok(())
+}
+
+/// Game::solve_to finds its way to the end of a random walk, in no more moves than the walk took.
+fn reach_walk(game: &Game) -> Result<(), String> {
+    let mut rng = Rng::new(game::zobrist_hash(game));
+    let mut target = game.clone();
+    let mut walk = vec![];
+    for _ in 0..8 {
+        let successors: Vec<_> = target.clone().iter_successors().collect();
+        if successors.is_empty() { break; }
+        let (successor, _cost, action) = successors[rng.below(successors.len())].clone();
+        target = successor;
+        walk.push(action);
+    }
+    let (path, _stats) = game.solve_to(&target).ok_or_else(|| format!("couldn't reach the end of the walk {:?}:\n{}", walk, target))?;
+    if path.len() > walk.len() {
+        return Err(format!("took {} moves to reach the end of the {} move walk {:?}", path.len(), walk.len(), walk));
+    }
+    let state = replay(game.clone(), &path)?;
+    if state != target {
+        return Err(format!("{:?} reached\n{}not\n{}", path, state, target));
+    }
+    Ok(())
+}
+
+/// Searching for partial goals with goal_streaks finds boards that meet them, in no more moves than solving,
+/// and in exactly as many when the goal asks for every color, or for the layout of the solved board.
+/// The heuristic never overestimates at the start.
+fn partial_goals(game: &Game) -> Result<(), String> {
+    // How the number of moves to meet each goal compares to the number to solve the game
+    enum Compared { Same, NoLonger, Unchecked }
+    use Compared::*;
+    let (compressed, _palette) = game.clone().compress();
+    let solution = astar::solve(compressed.clone(), h10s::compressed_dig_clutter).map(|(path, _stats)| path);
+    let solved = solution.as_ref().map(Vec::len);
+    let mut colors: Vec<Ball> = compressed.ball_counts().keys().cloned().collect();
+    colors.sort_by_key(|ball| ball.color);
+    let mut goals = vec![
+        (Goal::All, Same),
+        (Goal::Colors(colors.clone()), Same),
+        (Goal::Tubes(colors.len()), Same),
+        (Goal::Colors(colors[..1].to_vec()), NoLonger),
+        (Goal::Tubes(1), NoLonger),
+    ];
+    // The layout of the solved board is reached no quicker than solving; Otherwise solving could've been quicker.
+    if let Some(path) = &solution {
+        let state = replay(compressed.clone(), path)?;
+        let layout = state.tubes.iter().enumerate().filter_map(|(idx, tube)| tube.balls[0].map(|color| (idx, color))).collect();
+        goals.push((Goal::Layout(layout), Same));
+    }
+    // Any tube can be filled with any color, but that can take longer than solving, so that's unchecked.
+    goals.push((Goal::Layout(vec![(0, colors[0])]), Unchecked));
+    for (goal, compared) in goals.iter() {
+        compressed.check_goal(goal).map_err(|e| format!("{:?}: {}", goal, e))?;
+        let estimate = h10s::goal_streaks(&compressed, goal) as usize;
+        let found = astar::solve_until(compressed.clone(), |state| state.meets(goal), |state: &CompressedGame| h10s::goal_streaks(state, goal), &SolveOptions::default());
+        let len = found.as_ref().map(|(path, _stats)| path.len());
+        let wrong = match compared {
+            Same => len != solved,
+            NoLonger => solved.is_some() && len > solved,
+            Unchecked => false,
+        };
+        if wrong {
+            return Err(format!("{:?} took {:?} moves, but solving took {:?}", goal, len, solved));
+        }
+        if let Some((path, _stats)) = found {
+            if estimate > path.len() {
+                return Err(format!("goal_streaks estimated {} moves for {:?}, but it took {}", estimate, goal, path.len()));
+            }
+            let state = replay(compressed.clone(), &path)?;
+            if !state.meets(goal) {
+                return Err(format!("{:?} for {:?} reached\n{}", path, goal, *state));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// With every move costing 1, weighted search finds solutions as short as unweighted search;
+/// And with tubes scattered at random, it finds solutions no costlier than the unweighted solution,
+/// costing what the model says, and never costing less than weighted_dig_clutter estimates at the start.
+fn weighted_moves(game: &Game) -> Result<(), String> {
+    let mut rng = Rng::new(game::zobrist_hash(game));
+    let (compressed, _palette) = game.clone().compress();
+    let unweighted = astar::solve(compressed.clone(), h10s::compressed_dig_clutter).map(|(path, _stats)| path);
+
+    let uniform = CostModel{ overhead: 1, metric: Metric::Manhattan, positions: vec![(0, 0); game.tubes.len()] };
+    let estimate = h10s::weighted_dig_clutter(&compressed, &uniform.min_out());
+    if estimate != h10s::compressed_dig_clutter(&compressed) {
+        return Err(format!("weighted_dig_clutter gave {} with uniform costs, not {}", estimate, h10s::compressed_dig_clutter(&compressed)));
+    }
+    let found = weighted::solve(compressed.clone(), &uniform, &SolveOptions::default()).map(|(path, stats)| (path.len(), stats.path_cost));
+    let expected = unweighted.as_ref().map(|path| (path.len(), path.len() as game::Cost));
+    if found != expected {
+        return Err(format!("found (length, cost) {:?} with uniform costs, not {:?}", found, expected));
+    }
+
+    let metric = [Metric::Manhattan, Metric::Chebyshev, Metric::Euclidean][rng.below(3)];
+    let positions = game.tubes.iter().map(|_| (rng.below(20) as i32, rng.below(20) as i32)).collect();
+    let model = CostModel{ overhead: rng.below(5) as game::Cost, metric, positions };
+    let estimate = h10s::weighted_dig_clutter(&compressed, &model.min_out());
+    let (path, stats) = match weighted::solve(compressed.clone(), &model, &SolveOptions::default()) {
+        Some(solution) => solution,
+        None if unweighted.is_none() => return Ok(()),
+        None => return Err(format!("found no solution with {:?}, but there's one", model)),
+    };
+    let cost: game::Cost = path.iter().map(|&action| model.cost(action)).sum();
+    if cost != stats.path_cost || cost < estimate {
+        return Err(format!("{:?} cost {} by {:?}, but the search said {}, with an estimate of {}", path, cost, model, stats.path_cost, estimate));
+    }
+    let unweighted_cost: Option<game::Cost> = unweighted.map(|path| path.iter().map(|&action| model.cost(action)).sum());
+    let costlier = match unweighted_cost {
+        Some(unweighted_cost) => cost > unweighted_cost,
+        None => true,
+    };
+    if costlier {
+        return Err(format!("{:?} cost {} by {:?}, more than the unweighted solution's {:?}", path, cost, model, unweighted_cost));
+    }
+    let state = replay(compressed, &path)?;
+    if !state.is_solved() {
+        return Err(format!("{:?} reached\n{}", path, *state));
+    }
+    Ok(())
+}
+
+/// For each secondary objective, the search finds a solution as short as usual, costing what secondary::path_cost says,
+/// which is no more than the usual solution costs by that objective.
+fn secondary_objectives(game: &Game) -> Result<(), String> {
+    let (compressed, _palette) = game.clone().compress();
+    let usual = astar::solve(compressed.clone(), h10s::compressed_dig_clutter).map(|(path, _stats)| path);
+    for &objective in &[Objective::EmptyTubeUse, Objective::DirtyTubes, Objective::DistinctTubes] {
+        let found = secondary::solve(compressed.clone(), objective, &SolveOptions::default());
+        let (path, stats, usual) = match (found, &usual) {
+            (Some((path, stats)), Some(usual)) => (path, stats, usual),
+            (None, None) => continue,
+            (found, _) => return Err(format!("with {:?}, found {:?}, but solving found {:?}", objective, found.map(|(path, _stats)| path), usual)),
+        };
+        let cost = secondary::path_cost(compressed.clone(), objective, &path);
+        let usual_cost = secondary::path_cost(compressed.clone(), objective, usual);
+        if stats.path_cost != astar::Lex(usual.len() as game::Cost, cost) || cost > usual_cost {
+            return Err(format!("with {:?}, {:?} cost {} (the search said {}), but {:?} costs {}", objective, path, cost, stats.path_cost, usual, usual_cost));
+        }
+    }
+    Ok(())
+}
+
+/// With the empty tubes taken away, or one left, a failed search reports visiting every reachable board,
+/// as counted by a breadth first search; And a successful one finds a solution as short as usual.
+fn exhaustive_failure(game: &Game) -> Result<(), String> {
+    let mut tubes: Vec<Tube> = game.tubes.iter().filter(|tube| tube.balls[0].is_some()).cloned().collect();
+    for _ in 0..2 {
+        let (compressed, _palette) = Game{ tubes: tubes.clone() }.compress();
+        let outcome = astar::try_solve_until(compressed.clone(), State::is_solved, h10s::compressed_dig_clutter, &SolveOptions::default());
+        match outcome {
+            Ok((path, _stats)) => {
+                let usual = astar::solve(compressed.clone(), h10s::compressed_dig_clutter).map(|(path, _stats)| path.len());
+                if usual != Some(path.len()) {
+                    return Err(format!("try_solve_until found a {} move solution, but solve found {:?}", path.len(), usual));
+                }
+            }
+            Err(stats) => {
+                let mut reached = HashSet::new();
+                reached.insert(compressed.clone());
+                let mut queue = VecDeque::new();
+                queue.push_back(compressed.clone());
+                while let Some(state) = queue.pop_front() {
+                    for (next, _cost, _action) in state.iter_successors() {
+                        if reached.insert(next.clone()) { queue.push_back(next); }
+                    }
+                }
+                if stats.visited_len != reached.len() {
+                    return Err(format!("failed after visiting {} boards, but {} are reachable from\n{}", stats.visited_len, reached.len(), *compressed));
+                }
+            }
+        }
+        tubes.push(Tube::empty());
+    }
+    Ok(())
+}
+
+/// Names for named_game, both words and Unicode graphemes (including emoji that take several chars)
+const NAMES: [&str; 16] = [
+    "red", "blue", "green", "yellow", "dark-blue", "\"quoted\"", "back\\slash", "é",
+    "🔴", "🔵", "🟢", "🟡", "e\u{301}", "👍🏽", "🇨🇦", "👨\u{200D}👩\u{200D}👧",
+];
+
+/// `game`, with each of its colors given a random name from NAMES
+fn named_game(game: &Game) -> (Game, Palette) {
+    let mut rng = Rng::new(game.tubes.len() as u64);
+    let mut names = NAMES.to_vec();
+    rng.shuffle(&mut names);
+    let mut palette = Palette::default();
+    let mut renamed = HashMap::new();
+    let mut named = game.clone();
+    for tube in &mut named.tubes {
+        for ball in tube.balls.iter_mut().filter_map(|ball| ball.as_mut()) {
+            let next_name = names[renamed.len() % names.len()];
+            *ball = *renamed.entry(*ball).or_insert_with(|| palette.color(next_name).unwrap().unwrap());
+        }
+    }
+    (named, palette)
+}
+
+/// Reading back a game with named colors, written in the token syntax, gives the same game with the same names.
+fn named_text_round_trip(game: &Game) -> Result<(), String> {
+    let (named, palette) = named_game(game);
+    let text = named.to_text_with_palette(&palette);
+    let (parsed, parsed_palette) = Game::from_input_with_palette(&mut text.as_bytes());
+    let (expected, got) = (palette.display(&named).to_string(), parsed_palette.display(&parsed).to_string());
+    if expected != got {
+        return Err(format!("{}\nread back as:\n{}", expected, got));
+    }
+    Ok(())
+}
+
+/// Reading back a game with named colors, written as JSON, gives the same game with the same names.
+fn named_json_round_trip(game: &Game) -> Result<(), String> {
+    let (named, palette) = named_game(game);
+    let text = json::game(&named, &palette).to_string();
+    let (parsed, parsed_palette) = Json::parse(&text).and_then(|json| json::to_game(&json)).map_err(|e| format!("{} in {}", e, text))?;
+    if json::game(&parsed, &parsed_palette).to_string() != text {
+        return Err(format!("{} read back as:\n{}", text, parsed_palette.display(&parsed)));
+    }
+    Ok(())
+}
+
+/// A color mismatch in a game of named colors is described with the colors' names, rather than their ids.
+fn named_move_errors(game: &Game) -> Result<(), String> {
+    let (named, palette) = named_game(game);
+    let len = named.tubes.len() as u8;
+    for from in 0..len {
+        for to in 0..len {
+            let action = Action{ from, to };
+            if let Err(MoveError::ColorMismatch{ ball, onto }) = named.try_move(action) {
+                let e = MoveError::ColorMismatch{ ball, onto };
+                let expected = format!("can't put a '{}' ball onto a '{}' ball", palette.name(ball), palette.name(onto));
+                let got = e.to_string_with_palette(&palette);
+                if got != expected {
+                    return Err(format!("{} was described as {:?}, not {:?}", action, got, expected));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// A property of games, which returns a description of the problem if it doesn't hold
+type Property = fn(&Game) -> Result<(), String>;
+
+/// Every property, by name, with the most tubes of the games it's checked on (None for games of any size).
+/// Properties that solve the game skip bigger games, as they can take a while to solve.
+const PROPERTIES: [(&str, Property, Option<usize>); 18] = [
+    ("text round trip", text_round_trip, None),
+    ("short line round trip", short_line_round_trip, None),
+    ("JSON round trip", json_round_trip, None),
+    ("named text round trip", named_text_round_trip, None),
+    ("named JSON round trip", named_json_round_trip, None),
+    ("named move errors", named_move_errors, None),
+    ("compress round trip", compress_round_trip, None),
+    ("named compress round trip", named_compress_round_trip, None),
+    ("packed round trip", packed_round_trip, None),
+    ("incremental Zobrist hash", zobrist_incremental, None),
+    ("apply and undo", apply_undo, None),
+    ("work queues agree", work_queues_agree, Some(6)),
+    ("float costs", float_costs, Some(6)),
+    ("reach a walk's end", reach_walk, None),
+    ("partial goals", partial_goals, Some(6)),
+    // Weighted search, tracking the tubes touched, and exhausting the reachable boards each take longer still
+    ("weighted moves", weighted_moves, Some(5)),
+    ("secondary objectives", secondary_objectives, Some(5)),
+    ("exhaustive failure", exhaustive_failure, Some(5)),
+];
+
+/// Check each property against `game`, printing any failures. Returns the number of failures.
+fn check(game: &Game, case: &str) -> usize {
+    let mut failures = 0;
+    for (name, property, max_tubes) in PROPERTIES.iter() {
+        if let Some(max_tubes) = *max_tubes {
+            if game.tubes.len() > max_tubes { continue; }
+        }
+        // A panic (such as from Game::from_input) is a failure too
+        let result = panic::catch_unwind(AssertUnwindSafe(|| property(game)))
+            .unwrap_or_else(|payload| Err(format!("panicked: {}",
+                payload.downcast_ref::<String>().cloned()
+                    .or_else(|| payload.downcast_ref::<&str>().map(|s| s.to_string()))
+                    .unwrap_or_default())));
+        if let Err(e) = result {
+            println!("FAILED {} for {}:\n{}\n{}", name, case, game, e);
+            failures += 1;
+        }
+    }
+    failures
+}
+
+/// `balls selfcheck [LEVEL...] [--cases N] [--seed S]`
+///
+/// Exits with status 1 if any property fails.
+pub fn main(mut args: Vec<String>) {
+    let cases = take_option(&mut args, "--cases").map_or(1000, |cases| cases.parse().expect("--cases must be a number"));
+    let seed = take_option(&mut args, "--seed").map_or(479, |seed| seed.parse().expect("--seed must be a number"));
+
+    let levels: Vec<Game> = args.iter().map(|filename| with_input(filename, Game::from_input)).collect();
+
+    // Panics are reported as failures, so silence the default report
+    panic::set_hook(Box::new(|_| {}));
+
+    let mut failures = 0;
+    for (game, filename) in levels.iter().zip(&args) {
+        failures += check(game, filename);
+    }
+    // Each case gets its own seed, so a failing case can be rerun alone with `--cases 1 --seed S`
+    for case in 0..cases {
+        let case_seed = seed + case;
+        let game = random_game(&mut Rng::new(case_seed));
+        failures += check(&game, &format!("random game (--seed {})", case_seed));
+    }
+
+    let total = (args.len() + cases as usize) * PROPERTIES.len();
+    println!("{} of {} checks passed", total - failures, total);
+    if failures > 0 { std::process::exit(1); }
+}
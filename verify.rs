@@ -0,0 +1,130 @@
+//! Solution verifier: checks a list of moves against a level.
+//!
+//! The move list format is documented in README.md, under "Move List Format".
+use crate::game::{Action, Game, MoveError};
+use crate::astar::State;
+use crate::with_input;
+use crate::render::{RenderOptions, Renderer};
+use std::io::BufRead;
+
+/// A list of moves, as read from a move list file.
+pub struct MoveList {
+    /// The moves, converted to zero-based tube numbers
+    pub actions: Vec<Action>,
+    /// Line number of each action in the file, for error messages
+    pub lines: Vec<usize>,
+    /// Whether the file counts tubes from 1, for error messages
+    pub one_based: bool,
+}
+
+impl MoveList {
+    /// Parse a move list, or describe what's wrong with it: a line that isn't a move,
+    /// or a tube 0 in a list that numbers tubes from 1.
+    ///
+    /// # Panics
+    ///
+    /// Panics if BufRead::read_line fails on `input`.
+    pub fn from_input(input: &mut dyn BufRead) -> Result<MoveList, String> {
+        let mut actions = vec![];
+        let mut lines = vec![];
+        let mut one_based = None;
+        let mut line_no = 0;
+        loop {
+            let mut line = String::new();
+            let bytes_read = input.read_line(&mut line).expect("MoveList::from_input: Failed to read line from input stream. Potentially invalid UTF-8?");
+            if bytes_read == 0 { break; }
+            line_no += 1;
+
+            // Same whitespace and comment rules as the level format
+            let line = line.trim();
+            if line.is_empty() || line.starts_with("//") { continue; }
+
+            // The numbering directive may only come before the first move
+            if one_based.is_none() {
+                match line {
+                    "0-based" => { one_based = Some(false); continue; }
+                    "1-based" => { one_based = Some(true); continue; }
+                    _ => one_based = Some(false),
+                }
+            }
+
+            let action = match (Action::parse(line, false), one_based == Some(true)) {
+                (None, _) => return Err(format!("Expected a move like \"3->5\" on line {}, got: {:?}", line_no, line)),
+                (Some(action), false) => action,
+                (Some(_), true) => Action::parse(line, true).ok_or_else(|| format!(
+                    "Move {:?} on line {} has a tube 0, but this move list numbers tubes from 1 (it begins with \"1-based\")", line, line_no))?,
+            };
+            actions.push(action);
+            lines.push(line_no);
+        }
+        Ok(MoveList { actions, lines, one_based: one_based == Some(true) })
+    }
+}
+
+/// `balls verify LEVEL MOVES`
+///
+/// Replays MOVES onto LEVEL, reporting the first illegal move,
+/// whether the final state is solved, and how the solution compares to an optimal one.
+/// Exits with status 1 unless the moves are a legal solution.
+pub fn main(mut args: Vec<String>) {
+    let render_options = RenderOptions::from_args(&mut args);
+    if args.len() != 2 {
+        eprintln!("balls verify: Expected two arguments: level filename and move list filename (either may be '-' for stdin)");
+        std::process::exit(1);
+    }
+    let (game, palette) = with_input(&args[0], Game::from_input_with_palette);
+    let moves = with_input(&args[1], MoveList::from_input).unwrap_or_else(|e| {
+        eprintln!("balls verify: {}", e);
+        std::process::exit(1)
+    });
+    let renderer = Renderer::new(&game, &palette, &render_options);
+
+    // Replay the moves, stopping at the first illegal one
+    let mut state = game.clone();
+    let mut legal = true;
+    for (idx, (&action, line)) in moves.actions.iter().zip(&moves.lines).enumerate() {
+        match state.try_move(action) {
+            Ok(next_state) => state = next_state,
+            Err(e) => {
+                let e = match e {
+                    MoveError::OutOfRange => format!("{} (there are {} tubes)", e, state.tubes.len()),
+                    e => e.to_string_with_palette(&palette),
+                };
+                let base = if moves.one_based { 1 } else { 0 };
+                println!("Move {} ({}->{}, line {}) is illegal: {}",
+                    idx + 1, action.from as usize + base, action.to as usize + base, line, e);
+                println!("Board before the illegal move:\n{}", renderer.render(&state));
+                legal = false;
+                break;
+            }
+        }
+    }
+
+    let solved = legal && state.is_solved();
+    if legal {
+        println!("All {} moves are legal.", moves.actions.len());
+        if solved {
+            println!("The final state is solved.");
+        } else {
+            println!("The final state is not solved:\n{}", renderer.render(&state));
+        }
+    }
+
+    // Compare against an optimal solution
+    match game.solve() {
+        Some((path, _stats)) => {
+            let given = moves.actions.len();
+            let optimal = path.len();
+            if !solved {
+                println!("An optimal solution is {} moves long.", optimal);
+            } else if given == optimal {
+                println!("The solution is optimal ({} moves).", given);
+            } else {
+                println!("The solution is {} moves long, {} more than the optimal {}.", given, given - optimal, optimal);
+            }
+        }
+        None => println!("The level has no solution."),
+    }
+
+    if !solved { std::process::exit(1); }
+}
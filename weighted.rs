@@ -0,0 +1,143 @@
+//! Weighted moves, for a robot arm that sorts real tubes: a move costs the distance the arm travels between the tubes,
+//! plus an overhead for picking up and placing the ball.
+//!
+//! A level gives its cost model in comment lines beginning with `//@`, which every other tool skips like any other comment:
+//! ```text
+//! //@ cost 5 manhattan
+//! //@ tube 0 0
+//! //@ tube 10 0
+//! ```
+//! `cost OVERHEAD METRIC` sets the overhead (default 0) and the metric (default manhattan),
+//! and each `tube X Y` gives the position of the next tube, in the same order as the tube lines, empty tubes included.
+use crate::astar::{self, SolveOptions, SolveStats, State};
+use crate::game::{Action, CompressedGame, Cost};
+use crate::h10s;
+use std::hash::{Hash, Hasher};
+
+/// How the distance between two tubes is measured.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Metric {
+    /// The distances along each axis, added up: an arm that moves one axis at a time
+    Manhattan,
+    /// The longer of the distances along each axis: an arm that moves both axes at once
+    Chebyshev,
+    /// Straight line distance, rounded up to a whole unit so costs stay integers
+    Euclidean,
+}
+
+/// What each move costs: `overhead`, plus the distance from the source tube to the destination tube by `metric`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CostModel {
+    pub overhead: Cost,
+    pub metric: Metric,
+    /// Position of each tube, in whole units of whatever size suits (millimetres, say)
+    pub positions: Vec<(i32, i32)>,
+}
+
+impl CostModel {
+    /// Read the cost model from the `//@` lines of a level's text, or None if it has none.
+    ///
+    /// # Panics
+    ///
+    /// Panics on malformed `//@` lines, like Game::from_input does on malformed levels.
+    pub fn from_level(text: &str) -> Option<CostModel> {
+        let mut model = CostModel { overhead: 0, metric: Metric::Manhattan, positions: vec![] };
+        let mut found = false;
+        for line in text.lines().map(str::trim).filter(|line| line.starts_with("//@")) {
+            found = true;
+            let tokens: Vec<&str> = line["//@".len()..].split_whitespace().collect();
+            match tokens[..] {
+                ["cost", overhead, metric] => {
+                    model.overhead = overhead.parse().expect("Couldn't parse the overhead of a cost line");
+                    if model.overhead < 0 { panic!("A move can't have a negative overhead: {:?}", line); }
+                    model.metric = match metric {
+                        "manhattan" => Metric::Manhattan,
+                        "chebyshev" => Metric::Chebyshev,
+                        "euclidean" => Metric::Euclidean,
+                        _ => panic!("Expected manhattan, chebyshev, or euclidean, got line: {:?}", line),
+                    };
+                }
+                ["tube", x, y] => {
+                    let x = x.parse().expect("Couldn't parse a tube's x coordinate");
+                    let y = y.parse().expect("Couldn't parse a tube's y coordinate");
+                    model.positions.push((x, y));
+                }
+                _ => panic!("Expected `//@ cost OVERHEAD METRIC` or `//@ tube X Y`, got line: {:?}", line),
+            }
+        }
+        if found { Some(model) } else { None }
+    }
+
+    /// The cost of taking `action`.
+    pub fn cost(&self, action: Action) -> Cost {
+        let (x1, y1) = self.positions[action.from as usize];
+        let (x2, y2) = self.positions[action.to as usize];
+        let (dx, dy) = ((x1 - x2).abs(), (y1 - y2).abs());
+        let distance = match self.metric {
+            Metric::Manhattan => dx + dy,
+            Metric::Chebyshev => dx.max(dy),
+            Metric::Euclidean => (f64::from(dx).hypot(f64::from(dy))).ceil() as Cost,
+        };
+        self.overhead + distance
+    }
+
+    /// The cheapest move out of each tube, to any other tube.
+    pub fn min_out(&self) -> Vec<Cost> {
+        let tubes = self.positions.len() as u8;
+        (0..tubes).map(|from| {
+            (0..tubes).filter(|&to| to != from).map(|to| self.cost(Action{ from, to })).min().unwrap_or(0)
+        }).collect()
+    }
+}
+
+/// A compressed game whose moves cost what the CostModel says.
+#[derive(Clone, Debug)]
+pub struct WeightedGame<'a> {
+    pub game: CompressedGame,
+    model: &'a CostModel,
+}
+
+// Every WeightedGame in a search shares the same model, so only the game matters to equality and hashing.
+impl PartialEq for WeightedGame<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.game == other.game
+    }
+}
+
+impl Eq for WeightedGame<'_> {}
+
+impl Hash for WeightedGame<'_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.game.hash(state);
+    }
+}
+
+impl<'a> State for WeightedGame<'a> {
+    type Edge = Action;
+    type Cost = Cost;
+    type Iter = Box<dyn Iterator<Item = (WeightedGame<'a>, Cost, Action)> + 'a>;
+    fn iter_successors(self) -> Self::Iter {
+        let model = self.model;
+        Box::new(self.game.iter_successors().map(move |(game, _cost, action)| (WeightedGame{ game, model }, model.cost(action), action)))
+    }
+    fn try_edge(&self, edge: &Action) -> Option<Self> {
+        self.game.try_action(*edge).map(|game| WeightedGame{ game, model: self.model })
+    }
+    fn is_solved(&self) -> bool {
+        self.game.is_solved()
+    }
+    fn zobrist(&self) -> Option<u64> {
+        self.game.zobrist()
+    }
+}
+
+/// Find the cheapest solution to `game` by `model`, with A* and weighted_dig_clutter.
+///
+/// # Panics
+///
+/// Panics if the model doesn't give a position for every tube of the game.
+pub fn solve(game: CompressedGame, model: &CostModel, options: &SolveOptions) -> Option<(Vec<Action>, SolveStats<Cost>)> {
+    assert_eq!(model.positions.len(), game.tubes.len(), "The cost model needs a position for every tube");
+    let min_out = model.min_out();
+    astar::solve_with(WeightedGame{ game, model }, |state: &WeightedGame| h10s::weighted_dig_clutter(&state.game, &min_out), options)
+}